@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use twilight_model::channel::message::{Embed, MessageType};
+use twilight_model::channel::{Channel, Message};
+use twilight_model::id::marker::{
+    AttachmentMarker, ChannelMarker, GuildMarker, MessageMarker, UserMarker,
+};
+use twilight_model::id::Id;
+use twilight_model::user::User;
+use twilight_model::util::{ImageHash, Timestamp};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::attachments::format_bytes;
+use crate::message::{avatar_url, display_name};
+use crate::MessageInteractError::{ArchiveUnzipsTooLarge, InvalidArchive, UnsupportedArchiveVersion};
+use crate::{reactions, Context};
+
+/// Bumped when the format changes in a way older archives can't be read with
+pub const ARCHIVE_VERSION: u32 = 1;
+/// The name of the archive in a zip, next to the `attachments` folder
+pub const ARCHIVE_FILENAME: &str = "archive.json";
+/// What every zip starts with
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+/// The largest file read from a zip, a larger attachment couldn't be uploaded
+/// again anyway
+const MAX_UNZIPPED_FILE_SIZE: u64 = 100 * 1024 * 1024;
+/// The most read from a zip in total, it's all held in memory
+const MAX_UNZIPPED_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The messages of a channel, as they were when they were exported
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    pub guild_id: Option<Id<GuildMarker>>,
+    pub channel_id: Id<ChannelMarker>,
+    pub channel_name: Option<String>,
+    pub exported_at: Timestamp,
+    /// Oldest first
+    pub messages: Vec<ArchivedMessage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedMessage {
+    pub id: Id<MessageMarker>,
+    pub author: ArchivedAuthor,
+    pub content: String,
+    pub timestamp: Timestamp,
+    /// When the message was last edited, Discord doesn't keep the older
+    /// versions
+    pub edited_timestamp: Option<Timestamp>,
+    pub embeds: Vec<Embed>,
+    pub attachments: Vec<ArchivedAttachment>,
+    /// The message this one replies to
+    pub reference: Option<Id<MessageMarker>>,
+    pub reactions: Vec<ArchivedReaction>,
+    pub pinned: bool,
+    /// The names of the stickers
+    pub stickers: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedAuthor {
+    pub id: Id<UserMarker>,
+    pub name: String,
+    /// The nickname, or the global name if there's none
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    /// The hash of the avatar of the user, to send messages with it again
+    pub avatar: Option<ImageHash>,
+    pub bot: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedAttachment {
+    pub id: Id<AttachmentMarker>,
+    pub filename: String,
+    /// Stops working once the original message is deleted
+    pub url: String,
+    pub size: u64,
+    pub content_type: Option<String>,
+    pub description: Option<String>,
+    /// Where the file is in the zip, when the attachments were bundled
+    pub file: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedReaction {
+    pub emoji: String,
+    pub count: u64,
+}
+
+impl Archive {
+    pub fn new(channel: &Channel, messages: &[Message]) -> Result<Self> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+
+        Ok(Self {
+            version: ARCHIVE_VERSION,
+            guild_id: channel.guild_id,
+            channel_id: channel.id,
+            channel_name: channel.name.clone(),
+            exported_at: Timestamp::from_secs(i64::try_from(now)?)?,
+            messages: messages.iter().map(ArchivedMessage::from).collect(),
+        })
+    }
+}
+
+/// Reads an archive made by `/export_messages`, on its own or in a zip with
+/// its attachments, which are returned by their path. A zip that unzips to
+/// more than [`MAX_UNZIPPED_SIZE`] isn't read.
+pub fn read(bytes: Vec<u8>) -> Result<(Archive, HashMap<String, Vec<u8>>)> {
+    let mut files = HashMap::new();
+    let json = if bytes.starts_with(ZIP_MAGIC) {
+        let mut zip = ZipArchive::new(Cursor::new(bytes)).map_err(|_| anyhow!(InvalidArchive))?;
+        let mut unzipped_size = 0;
+        for idx in 0..zip.len() {
+            let mut file = zip.by_index(idx)?;
+            let max_size = MAX_UNZIPPED_FILE_SIZE.min(MAX_UNZIPPED_SIZE - unzipped_size);
+            let too_large = || {
+                anyhow!(ArchiveUnzipsTooLarge(
+                    format_bytes(MAX_UNZIPPED_FILE_SIZE),
+                    format_bytes(MAX_UNZIPPED_SIZE)
+                ))
+            };
+            if file.size() > max_size {
+                return Err(too_large());
+            }
+            // The size the zip gives can be a lie, reading stops past it
+            let mut content = Vec::new();
+            (&mut file).take(max_size + 1).read_to_end(&mut content)?;
+            let size = content.len() as u64;
+            if size > max_size {
+                return Err(too_large());
+            }
+            unzipped_size += size;
+            files.insert(file.name().to_owned(), content);
+        }
+        files.remove(ARCHIVE_FILENAME).ok_or_else(|| anyhow!(InvalidArchive))?
+    } else {
+        bytes
+    };
+
+    let version = serde_json::from_slice::<ArchiveVersion>(&json)
+        .map_err(|_| anyhow!(InvalidArchive))?
+        .version;
+    if version > ARCHIVE_VERSION {
+        return Err(anyhow!(UnsupportedArchiveVersion(version)));
+    }
+    let archive = serde_json::from_slice(&json).map_err(|_| anyhow!(InvalidArchive))?;
+
+    Ok((archive, files))
+}
+
+/// Read first, so that a newer archive isn't reported as invalid
+#[derive(Deserialize)]
+struct ArchiveVersion {
+    version: u32,
+}
+
+impl ArchivedMessage {
+    /// The message to send again in `channel_id`, with a note for each
+    /// sticker since only their name is kept. Replies are left to the
+    /// caller, the replied message has to be sent first.
+    pub fn to_message(&self, channel_id: Id<ChannelMarker>, guild_id: Option<Id<GuildMarker>>) -> Message {
+        let mut content = self.content.clone();
+        if !self.stickers.is_empty() {
+            if !content.is_empty() {
+                content.push('\n');
+            }
+            let notes = self
+                .stickers
+                .iter()
+                .map(|sticker| format!("*sticker: {sticker}*"))
+                .collect::<Vec<_>>();
+            content.push_str(&notes.join("\n"));
+        }
+
+        Message {
+            activity: None,
+            application: None,
+            application_id: None,
+            attachments: Vec::new(),
+            author: User {
+                accent_color: None,
+                avatar: self.author.avatar,
+                avatar_decoration: None,
+                banner: None,
+                bot: self.author.bot,
+                discriminator: 0,
+                email: None,
+                flags: None,
+                global_name: Some(self.author.display_name.clone()),
+                id: self.author.id,
+                locale: None,
+                mfa_enabled: None,
+                name: self.author.name.clone(),
+                premium_type: None,
+                public_flags: None,
+                system: None,
+                verified: None,
+            },
+            channel_id,
+            components: Vec::new(),
+            content,
+            edited_timestamp: self.edited_timestamp,
+            embeds: self.embeds.clone(),
+            flags: None,
+            guild_id,
+            id: self.id,
+            interaction: None,
+            kind: MessageType::Regular,
+            member: None,
+            mention_channels: Vec::new(),
+            mention_everyone: false,
+            mention_roles: Vec::new(),
+            mentions: Vec::new(),
+            pinned: self.pinned,
+            reactions: Vec::new(),
+            reference: None,
+            referenced_message: None,
+            role_subscription_data: None,
+            sticker_items: Vec::new(),
+            timestamp: self.timestamp,
+            thread: None,
+            tts: false,
+            webhook_id: None,
+        }
+    }
+}
+
+impl From<&Message> for ArchivedMessage {
+    fn from(message: &Message) -> Self {
+        Self {
+            id: message.id,
+            author: ArchivedAuthor {
+                id: message.author.id,
+                name: message.author.name.clone(),
+                display_name: display_name(message),
+                avatar_url: avatar_url(message),
+                avatar: message.author.avatar,
+                bot: message.author.bot,
+            },
+            content: message.content.clone(),
+            timestamp: message.timestamp,
+            edited_timestamp: message.edited_timestamp,
+            embeds: message.embeds.clone(),
+            attachments: message
+                .attachments
+                .iter()
+                .map(|attachment| ArchivedAttachment {
+                    id: attachment.id,
+                    filename: attachment.filename.clone(),
+                    url: attachment.url.clone(),
+                    size: attachment.size,
+                    content_type: attachment.content_type.clone(),
+                    description: attachment.description.clone(),
+                    file: None,
+                })
+                .collect(),
+            reference: message
+                .reference
+                .as_ref()
+                .and_then(|reference| reference.message_id),
+            reactions: message
+                .reactions
+                .iter()
+                .map(|reaction| ArchivedReaction {
+                    emoji: reactions::display(&reaction.emoji),
+                    count: reaction.count,
+                })
+                .collect(),
+            pinned: message.pinned,
+            stickers: message
+                .sticker_items
+                .iter()
+                .map(|sticker| sticker.name.clone())
+                .collect(),
+        }
+    }
+}
+
+impl Context {
+    /// A zip with `archive` and the attachments of its messages, which are
+    /// downloaded while the original messages still exist
+    pub async fn zip_archive(&self, mut archive: Archive, messages: &[Message]) -> Result<Vec<u8>> {
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        for (archived, message) in archive.messages.iter_mut().zip(messages) {
+            for (archived_attachment, attachment) in
+                archived.attachments.iter_mut().zip(&message.attachments)
+            {
+                let file = self.download_attachment(attachment).await?;
+                let path = format!("attachments/{}/{}", attachment.id, file.filename);
+                zip.start_file(path.as_str(), options)?;
+                zip.write_all(&file.file)?;
+                archived_attachment.file = Some(path);
+            }
+        }
+
+        zip.start_file(ARCHIVE_FILENAME, options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&archive)?)?;
+
+        Ok(zip.finish()?.into_inner())
+    }
+}
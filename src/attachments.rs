@@ -0,0 +1,177 @@
+use std::env;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use twilight_model::{
+    channel::{Attachment, Channel},
+    guild::PremiumTier,
+    http::attachment,
+    id::{marker::GuildMarker, Id},
+};
+
+use crate::{extras::StickerFile, Context};
+
+/// What anyone can upload, in a guild without boosts or at level 1
+const DEFAULT_UPLOAD_LIMIT: u64 = 10 * 1024 * 1024;
+const TIER_2_UPLOAD_LIMIT: u64 = 50 * 1024 * 1024;
+const TIER_3_UPLOAD_LIMIT: u64 = 100 * 1024 * 1024;
+
+/// What to do with attachments that are too large to be uploaded to the
+/// target guild, picked for each move or set with `LARGE_ATTACHMENTS`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum LargeAttachments {
+    /// Post the link of the original attachment instead, it stops working
+    /// once the original message is deleted so moves keep the original then
+    #[default]
+    Link,
+    /// Send the attachments in as many messages as needed, a single file
+    /// too large on its own is linked
+    Split,
+    /// Leave the attachment out, with a note saying so, moves keep the
+    /// original
+    Skip,
+}
+
+impl LargeAttachments {
+    pub fn from_env() -> Self {
+        match env::var("LARGE_ATTACHMENTS").as_deref() {
+            Ok("split") => Self::Split,
+            Ok("skip") => Self::Skip,
+            _ => Self::Link,
+        }
+    }
+
+    /// Splits `files` into messages that fit `limit`, the ones that don't
+    /// fit are handled with `self`
+    pub fn plan<'a>(self, files: &[PlannedFile<'a>], limit: u64) -> AttachmentPlan<'a> {
+        let mut plan = AttachmentPlan::default();
+        let mut group_size = 0;
+
+        for file in files {
+            let fits_group = plan
+                .groups
+                .last()
+                .is_some_and(|_| group_size + file.size() <= limit);
+            if fits_group {
+                plan.groups.last_mut().unwrap().push(*file);
+                group_size += file.size();
+                continue;
+            }
+
+            let fits_alone = file.size() <= limit;
+            if fits_alone && (plan.groups.is_empty() || self == Self::Split) {
+                plan.groups.push(vec![*file]);
+                group_size = file.size();
+            } else if self == Self::Skip {
+                plan.notes.push(format!(
+                    "*{} ({}) was too large to be moved*",
+                    file.filename(),
+                    format_bytes(file.size())
+                ));
+            } else {
+                plan.notes.push(file.url().to_owned());
+            }
+        }
+
+        plan
+    }
+}
+
+/// A file to send with the copy of a message
+#[derive(Clone, Copy, Debug)]
+pub enum PlannedFile<'a> {
+    Attachment(&'a Attachment),
+    /// Stickers are sent as images, already downloaded to know their size
+    Sticker(&'a StickerFile),
+}
+
+impl PlannedFile<'_> {
+    const fn size(&self) -> u64 {
+        match self {
+            Self::Attachment(attachment) => attachment.size,
+            Self::Sticker(sticker) => sticker.image.len() as u64,
+        }
+    }
+
+    fn filename(&self) -> &str {
+        match self {
+            Self::Attachment(attachment) => &attachment.filename,
+            Self::Sticker(sticker) => &sticker.filename,
+        }
+    }
+
+    fn url(&self) -> &str {
+        match self {
+            Self::Attachment(attachment) => &attachment.url,
+            Self::Sticker(sticker) => &sticker.url,
+        }
+    }
+}
+
+/// How the attachments of a message are sent
+#[derive(Debug, Default)]
+pub struct AttachmentPlan<'a> {
+    /// The files of each message to send, the first one is sent with the
+    /// content
+    pub groups: Vec<Vec<PlannedFile<'a>>>,
+    /// The lines to add to the content for the attachments that aren't
+    /// uploaded
+    pub notes: Vec<String>,
+}
+
+impl Context {
+    /// How large the files of a message sent to `channel` can be, its guild
+    /// is only asked for when `files` wouldn't fit the default limit
+    pub async fn upload_limit(&self, files: &[PlannedFile<'_>], channel: &Channel) -> Result<u64> {
+        let total: u64 = files.iter().map(PlannedFile::size).sum();
+        let Some(guild_id) = channel.guild_id.filter(|_| total > DEFAULT_UPLOAD_LIMIT) else {
+            return Ok(DEFAULT_UPLOAD_LIMIT);
+        };
+
+        self.guild_upload_limit(guild_id).await
+    }
+
+    /// How large the files uploaded to `guild_id` can be, with its boosts
+    pub async fn guild_upload_limit(&self, guild_id: Id<GuildMarker>) -> Result<u64> {
+        let guild = self.bot.http.guild(guild_id).await?.model().await?;
+        Ok(match guild.premium_tier {
+            PremiumTier::Tier2 => TIER_2_UPLOAD_LIMIT,
+            PremiumTier::Tier3 => TIER_3_UPLOAD_LIMIT,
+            _ => DEFAULT_UPLOAD_LIMIT,
+        })
+    }
+
+    /// Downloads the files of one message, so that only the files of the
+    /// message being sent are held in memory
+    pub async fn download_group(&self, files: &[PlannedFile<'_>]) -> Result<Vec<attachment::Attachment>> {
+        let mut http_attachments = Vec::new();
+        for file in files {
+            http_attachments.push(match file {
+                PlannedFile::Attachment(channel_attachment) => {
+                    self.download_attachment(channel_attachment).await?
+                }
+                PlannedFile::Sticker(sticker) => sticker.attachment(),
+            });
+        }
+
+        Ok(http_attachments)
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,225 @@
+use crate::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use std::time::Duration;
+use twilight_model::channel::message::sticker::{MessageSticker, StickerFormatType};
+use twilight_model::channel::message::{Embed, MessageFlags, MessageType};
+use twilight_model::channel::Message;
+use twilight_model::http::attachment;
+use twilight_model::id::marker::StickerMarker;
+use twilight_model::id::Id;
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFooterBuilder};
+
+/// The poll of a message, twilight doesn't know about polls yet
+#[derive(Debug, Deserialize)]
+struct Poll {
+    question: PollMedia,
+    answers: Vec<PollAnswer>,
+    results: Option<PollResults>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PollMedia {
+    text: Option<String>,
+    emoji: Option<PollEmoji>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PollEmoji {
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PollAnswer {
+    answer_id: u64,
+    poll_media: PollMedia,
+}
+
+#[derive(Debug, Deserialize)]
+struct PollResults {
+    is_finalized: bool,
+    answer_counts: Vec<PollAnswerCount>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PollAnswerCount {
+    id: u64,
+    count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct PollMessage {
+    poll: Option<Poll>,
+}
+
+/// The image of a sticker, to send it as an attachment
+#[derive(Debug)]
+pub struct StickerFile {
+    pub id: Id<StickerMarker>,
+    pub filename: String,
+    pub url: String,
+    pub image: Vec<u8>,
+}
+
+impl StickerFile {
+    pub fn attachment(&self) -> attachment::Attachment {
+        attachment::Attachment::from_bytes(self.filename.clone(), self.image.clone(), self.id.get())
+    }
+}
+
+impl PollMedia {
+    fn display(&self) -> String {
+        let emoji = self.emoji.as_ref().and_then(|emoji| emoji.name.as_deref());
+        match (emoji, self.text.as_deref()) {
+            (Some(emoji), Some(text)) => format!("{emoji} {text}"),
+            (Some(emoji), None) => emoji.to_owned(),
+            (None, text) => text.unwrap_or_default().to_owned(),
+        }
+    }
+}
+
+impl Context {
+    /// Turns the parts of `message` a webhook can't send into something it
+    /// can: polls become an embed with their votes, stickers are sent as
+    /// images with [`download_stickers`]
+    ///
+    /// Voice messages can't be kept: webhooks can't set
+    /// `IS_VOICE_MESSAGE` and twilight's attachments have no waveform or
+    /// duration, so the audio is sent as a file with a note of its duration
+    /// and the move report says so.
+    pub async fn with_extras(&self, message: &Message) -> Result<Message> {
+        let mut message = message.clone();
+        let mut notes = Vec::new();
+
+        if is_voice_message(&message) {
+            let duration = message
+                .attachments
+                .first()
+                .and_then(|attachment| attachment.duration_secs)
+                .and_then(|secs| Duration::try_from_secs_f64(secs.round()).ok())
+                .map_or_else(String::new, |duration| {
+                    let secs = duration.as_secs();
+                    format!(" ({}:{:02})", secs / 60, secs % 60)
+                });
+            notes.push(format!("*voice message{duration}*"));
+        }
+
+        if let Some(poll_embed) = self.poll_embed(&message).await? {
+            message.embeds.push(poll_embed);
+        }
+
+        if !notes.is_empty() {
+            if !message.content.is_empty() {
+                message.content.push('\n');
+            }
+            message.content.push_str(&notes.join("\n"));
+        }
+
+        Ok(message)
+    }
+
+    /// The poll of `message` with its votes, only asked to Discord for
+    /// messages that would be empty otherwise since polls can't have content
+    async fn poll_embed(&self, message: &Message) -> Result<Option<Embed>> {
+        if message.kind != MessageType::Regular
+            || !message.content.is_empty()
+            || !message.attachments.is_empty()
+            || !message.embeds.is_empty()
+            || !message.sticker_items.is_empty()
+        {
+            return Ok(None);
+        }
+
+        let raw = self
+            .bot
+            .http
+            .message(message.channel_id, message.id)
+            .await?
+            .text()
+            .await?;
+        let Some(poll) = serde_json::from_str::<PollMessage>(&raw)?.poll else {
+            return Ok(None);
+        };
+
+        let results = poll.results.as_ref();
+        let answers = poll
+            .answers
+            .iter()
+            .map(|answer| {
+                let votes = results
+                    .and_then(|results| {
+                        results
+                            .answer_counts
+                            .iter()
+                            .find(|count| count.id == answer.answer_id)
+                    })
+                    .map_or(0, |count| count.count);
+                format!(
+                    "{}: **{votes}** vote{}",
+                    answer.poll_media.display(),
+                    if votes == 1 { "" } else { "s" }
+                )
+            })
+            .collect::<Vec<_>>();
+        let footer = if results.is_some_and(|results| results.is_finalized) {
+            "poll, final results"
+        } else {
+            "poll, results when it was moved"
+        };
+
+        Ok(Some(
+            EmbedBuilder::new()
+                .title(poll.question.display())
+                .description(answers.join("\n"))
+                .footer(EmbedFooterBuilder::new(footer))
+                .build(),
+        ))
+    }
+}
+
+/// The images of the stickers of `message`, with a note naming the ones
+/// that can't be downloaded
+pub async fn download_stickers(message: &Message) -> (Vec<StickerFile>, Vec<String>) {
+    let mut stickers = Vec::new();
+    let mut notes = Vec::new();
+    for sticker in &message.sticker_items {
+        match download_sticker(sticker).await {
+            Some(sticker_file) => stickers.push(sticker_file),
+            None => notes.push(format!("*sticker: {}*", sticker.name)),
+        }
+    }
+
+    (stickers, notes)
+}
+
+/// Downloads `sticker` as an image, lottie stickers can't be shown as one
+async fn download_sticker(sticker: &MessageSticker) -> Option<StickerFile> {
+    let extension = match sticker.format_type {
+        StickerFormatType::Png | StickerFormatType::Apng => "png",
+        StickerFormatType::Gif => "gif",
+        _ => return None,
+    };
+
+    let url = format!("https://media.discordapp.net/stickers/{}.{extension}", sticker.id);
+    let image = reqwest::get(&url)
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .bytes()
+        .await
+        .ok()?;
+
+    Some(StickerFile {
+        id: sticker.id,
+        filename: format!("{}.{extension}", sticker.name),
+        url,
+        image: image.to_vec(),
+    })
+}
+
+pub fn is_voice_message(message: &Message) -> bool {
+    message
+        .flags
+        .is_some_and(|flags| flags.contains(MessageFlags::IS_VOICE_MESSAGE))
+}
@@ -6,16 +6,32 @@ use sparkle_convenience::{
     interaction::{extract::InteractionExt, InteractionHandle},
     Bot,
 };
-use twilight_model::application::interaction::Interaction;
+use twilight_model::application::command::Command;
+use twilight_model::application::interaction::{Interaction, InteractionType};
 
 mod channel_select_menu;
 mod message_command;
 mod move_channel_select;
 mod move_message;
 mod move_message_and_below;
+pub mod move_range;
+mod move_recent;
 mod move_to_channel;
+mod move_to_new_thread;
 mod move_to_until;
+pub mod undo_move;
 mod delete_messages;
+mod dry_run;
+mod export_messages;
+mod failure;
+mod filter;
+mod forum;
+mod import_messages;
+mod keep_reactions;
+mod large_attachments;
+mod mirror;
+mod move_options;
+mod other_server;
 mod utils;
 
 struct InteractionContext<'ctx> {
@@ -26,24 +42,46 @@ struct InteractionContext<'ctx> {
 
 impl<'ctx> InteractionContext<'ctx> {
     async fn _handle(self) -> Result<()> {
+        // The servers and channels to move to are the only lists filled as
+        // the user types
+        if self.interaction.kind == InteractionType::ApplicationCommandAutocomplete {
+            return self.handle_other_server_autocomplete().await;
+        }
         match self.interaction.name().ok()? {
-            move_message::NAME => self.handle_move_message_command().await,
-            move_message_and_below::NAME => self.handle_move_message_and_below_command().await,
-            move_message::CHAT_INPUT_NAME => self.handle_command_call().await,
-            move_message_and_below::CHAT_INPUT_NAME => self.handle_move_and_below_command_call().await,
-            move_message::CHAT_INPUT_NAME_2 => self.handle_command_call().await,
-            move_message_and_below::CHAT_INPUT_NAME_2 => self.handle_move_and_below_command_call().await,
-            move_to_channel::CHAT_INPUT_NAME => self.handle_move_channel_call().await,
-            move_to_until::CHAT_INPUT_NAME => self.handle_move_to_until_cmd().await,
-            delete_messages::CHAT_INPUT_NAME => self.handle_delete_cmd().await,
+            move_message::NAME => Box::pin(self.handle_move_message_command()).await,
+            move_message_and_below::NAME => Box::pin(self.handle_move_message_and_below_command()).await,
+            move_range::MARK_START_NAME => Box::pin(self.handle_mark_range_start_command()).await,
+            move_range::MOVE_RANGE_NAME => Box::pin(self.handle_move_range_command()).await,
+            move_to_new_thread::NAME => Box::pin(self.handle_move_to_new_thread_command()).await,
             move_channel_select::CUSTOM_ID => Ok(()),
+            _ => Box::pin(self.handle_chat_input()).await,
+        }
+    }
+
+    /// Split from `_handle` so that the futures of every command aren't on
+    /// the same stack frame
+    async fn handle_chat_input(self) -> Result<()> {
+        match self.interaction.name().ok()? {
+            move_message::CHAT_INPUT_NAME => Box::pin(self.handle_command_call()).await,
+            move_message_and_below::CHAT_INPUT_NAME => Box::pin(self.handle_move_and_below_command_call()).await,
+            move_message::CHAT_INPUT_NAME_2 => Box::pin(self.handle_command_call()).await,
+            move_message_and_below::CHAT_INPUT_NAME_2 => Box::pin(self.handle_move_and_below_command_call()).await,
+            move_to_channel::CHAT_INPUT_NAME => Box::pin(self.handle_move_channel_call()).await,
+            move_to_until::CHAT_INPUT_NAME => Box::pin(self.handle_move_to_until_cmd()).await,
+            move_recent::CHAT_INPUT_NAME => Box::pin(self.handle_move_recent_cmd()).await,
+            move_to_new_thread::CHAT_INPUT_NAME => Box::pin(self.handle_move_to_new_thread_cmd()).await,
+            delete_messages::CHAT_INPUT_NAME => Box::pin(self.handle_delete_cmd()).await,
+            export_messages::CHAT_INPUT_NAME => Box::pin(self.handle_export_messages_cmd()).await,
+            import_messages::CHAT_INPUT_NAME => Box::pin(self.handle_import_messages_cmd()).await,
+            undo_move::CHAT_INPUT_NAME => Box::pin(self.handle_undo_move_cmd()).await,
+            mirror::CHAT_INPUT_NAME => Box::pin(self.handle_mirror_cmd()).await,
             name => Err(Error::UnknownCommand(name.to_owned()).into()),
         }
     }
 
     pub async fn handle(self) -> Result<()> {
         let handle = self.handle.clone();
-        match self._handle().await {
+        match Box::pin(self._handle()).await {
             Ok(_) => Ok(()),
             Err(err) => {
                 if let Some(interaction_error) = err.downcast_ref::<MessageInteractError>() {
@@ -58,19 +96,32 @@ impl<'ctx> InteractionContext<'ctx> {
     
 }
 
-pub async fn set_commands(bot: &Bot) -> Result<()> {
-    let commands = &[
+fn commands() -> Vec<Command> {
+    vec![
         move_message::command(), 
         move_message_and_below::command(), 
+        move_range::mark_start_command(),
+        move_range::move_range_command(),
         move_message::slash_command(), 
         move_message_and_below::slash_command(), 
         move_message::slash_command2(), 
         move_message_and_below::slash_command2(), 
         move_to_channel::slash_command(),
         move_to_until::slashCommand(),
+        move_recent::slash_command(),
+        move_to_new_thread::command(),
+        move_to_new_thread::slash_command(),
         delete_messages::slashCommand(),
-    ];
-    
+        export_messages::slash_command(),
+        import_messages::slash_command(),
+        undo_move::slash_command(),
+        mirror::slash_command(),
+    ]
+}
+
+pub async fn set_commands(bot: &Bot) -> Result<()> {
+    let commands = &commands();
+
     bot.interaction_client()
         .set_global_commands(commands)
         .await?;
@@ -84,16 +135,40 @@ pub async fn set_commands(bot: &Bot) -> Result<()> {
 impl Context {
     pub async fn handle_interaction(&self, interaction: Interaction) {
         let handle = self.bot.interaction_handle(&interaction);
+        let interaction_id = interaction.id;
+        let user_id = interaction.author_id();
         let ctx = InteractionContext {
             ctx: self,
             handle: handle.clone(),
             interaction,
         };
 
-        if let Err(err) = ctx.handle().await {
+        let result = Box::pin(ctx.handle()).await;
+        if let Some(user_id) = user_id {
+            self.finish_move_record(user_id, interaction_id);
+        }
+        if let Err(err) = result {
             handle
                 .handle_error::<CustomError>(err_reply(&err), err)
                 .await;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::commands;
+
+    /// Discord rejects the whole set if one command is invalid, and the bot
+    /// can't start without its commands
+    #[test]
+    fn commands_are_valid() {
+        for command in commands() {
+            let validation = twilight_validate::command::command(&command)
+                .and_then(|()| twilight_validate::command::options(&command.options));
+            if let Err(err) = validation {
+                panic!("{} is invalid: {err}", command.name);
+            }
+        }
+    }
+}
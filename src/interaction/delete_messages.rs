@@ -1,8 +1,9 @@
 use crate::interaction::move_message_and_below::parse_message_link;
-use crate::interaction::InteractionContext;
-use crate::MessageInteractError::NotInSameChannel;
+use crate::interaction::{dry_run, filter, InteractionContext};
+use crate::MessageInteractError::{NotBoth, NotInSameChannel};
 use crate::REQUIRED_PERMISSIONS;
 use anyhow::{anyhow, Result};
+use sparkle_convenience::error::IntoError;
 use sparkle_convenience::interaction::extract::InteractionDataExt;
 use sparkle_convenience::reply::Reply;
 use std::collections::HashMap;
@@ -31,11 +32,14 @@ pub fn slashCommand() -> Command {
         .default_member_permissions(REQUIRED_PERMISSIONS)
         .dm_permission(false)
         .option(CommandOption::from(
-            StringBuilder::new("message_from", "Source message url").required(true),
+            StringBuilder::new("message_from", "Source message url, or use after and before").required(false),
         ))
         .option(CommandOption::from(
             StringBuilder::new("message_to", "Source message url end").required(false),
         ))
+        .option(filter::after_option())
+        .option(filter::before_option())
+        .option(dry_run::option())
         //.default_member_permissions(REQUIRED_PERMISSIONS)
         .description_localizations(map.iter())
         .build()
@@ -47,6 +51,9 @@ impl InteractionContext<'_> {
         let mut from_message: Option<Id<MessageMarker>> = None;
         let mut channel: Option<Id<ChannelMarker>> = None;
         let mut to_message: Option<Id<MessageMarker>> = None;
+        let mut after: Option<i64> = None;
+        let mut before: Option<i64> = None;
+        let mut dry_run = false;
         if let Some(data) = self.interaction.data.clone() {
             if let Some(command_data) = data.command() {
                 for option in &command_data.options {
@@ -79,16 +86,57 @@ impl InteractionContext<'_> {
                                 }
                             }
                         }
+                        filter::AFTER_OPTION_NAME => {
+                            if let CommandOptionValue::String(time) = &option.value {
+                                after = Some(filter::parse_time(time)?);
+                            }
+                        }
+                        filter::BEFORE_OPTION_NAME => {
+                            if let CommandOptionValue::String(time) = &option.value {
+                                before = Some(filter::parse_time(time)?);
+                            }
+                        }
+                        dry_run::OPTION_NAME => {
+                            if let CommandOptionValue::Boolean(b) = &option.value {
+                                dry_run = *b;
+                            }
+                        }
                         _ => {}
                     }
                 }
             }
         }
-        
-        let from_message = from_message.ok_or_else(|| anyhow!("No message from"))?;
-        let channel = channel.ok_or_else(|| anyhow!("No channel"))?;
+
+        // The links and the time window both pick the messages, one of them
+        // would be ignored
+        let link = match (from_message, to_message) {
+            (Some(_), _) => Some("message_from"),
+            (None, Some(_)) => Some("message_to"),
+            (None, None) => None,
+        };
+        let time = match (after, before) {
+            (Some(_), _) => Some(filter::AFTER_OPTION_NAME),
+            (None, Some(_)) => Some(filter::BEFORE_OPTION_NAME),
+            (None, None) => None,
+        };
+        if let (Some(link), Some(time)) = (link, time) {
+            return Err(anyhow!(NotBoth(link.to_string(), time.to_string())));
+        }
+
+        // Without links, the messages are taken from the current channel
+        let channel = match channel {
+            Some(channel) => channel,
+            None => self.interaction.channel.as_ref().ok()?.id,
+        };
+        if dry_run {
+            self.handle.reply(Reply::new().ephemeral().update_last().content("Collecting messages...")).await?;
+            let list = self.get_message_range(channel, from_message, to_message, (after, before)).await?;
+            return self
+                .reply_dry_run(&list, self.interaction.guild_id, &["all of these messages would be deleted".to_owned()])
+                .await;
+        }
         self.handle.reply(Reply::new().ephemeral().update_last().content("Deleting messages...")).await?;
-        let list = self.get_message_borned(channel, from_message, to_message).await?;
+        let list = self.get_message_range(channel, from_message, to_message, (after, before)).await?;
         self.handle.reply(Reply::new().ephemeral().update_last().content(format!("Found {} messages...", list.len()))).await?;
         self.bulk_delete(list, None).await?;
         self.handle.reply(Reply::new().ephemeral().update_last().content("Done!")).await?;
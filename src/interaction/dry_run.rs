@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use sparkle_convenience::reply::Reply;
+use twilight_model::application::command::CommandOption;
+use twilight_model::channel::Message;
+use twilight_model::id::marker::GuildMarker;
+use twilight_model::id::Id;
+use twilight_util::builder::command::BooleanBuilder;
+
+use crate::attachments::format_bytes;
+use crate::interaction::utils::message_link;
+use crate::interaction::InteractionContext;
+use crate::message::is_outside_bulk_delete_window;
+
+pub const OPTION_NAME: &str = "dry_run";
+
+/// Authors listed by name in the summary, the rest are only counted.
+const MAX_LISTED_AUTHORS: usize = 15;
+
+pub fn option() -> CommandOption {
+    CommandOption::from(BooleanBuilder::new(
+        OPTION_NAME,
+        "only show what would happen, nothing is sent or deleted",
+    ))
+}
+
+impl InteractionContext<'_> {
+    /// Replies with what a move or delete would do to `messages`, `actions`
+    /// are extra lines describing the side effects of the command.
+    pub async fn reply_dry_run(
+        &self,
+        messages: &[Message],
+        guild_id: Option<Id<GuildMarker>>,
+        actions: &[String],
+    ) -> Result<()> {
+        self.handle
+            .reply(
+                Reply::new()
+                    .ephemeral()
+                    .update_last()
+                    .content(dry_run_summary(messages, guild_id, actions)),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn dry_run_summary(
+    messages: &[Message],
+    guild_id: Option<Id<GuildMarker>>,
+    actions: &[String],
+) -> String {
+    let mut lines = vec![
+        "**dry run**, nothing was sent or deleted".to_owned(),
+        format!("messages: {}", messages.len()),
+    ];
+    if let (Some(first), Some(last)) = (messages.first(), messages.last()) {
+        lines.push(format!("first: {}", message_link(guild_id, first)));
+        lines.push(format!("last: {}", message_link(guild_id, last)));
+    }
+
+    let mut authors: Vec<(&str, usize)> = Vec::new();
+    let mut author_indexes = HashMap::new();
+    for message in messages {
+        let idx = *author_indexes.entry(message.author.id).or_insert_with(|| {
+            authors.push((message.author.name.as_str(), 0));
+            authors.len() - 1
+        });
+        authors[idx].1 += 1;
+    }
+    authors.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let mut listed = authors
+        .iter()
+        .take(MAX_LISTED_AUTHORS)
+        .map(|(name, count)| format!("{name} ({count})"))
+        .collect::<Vec<_>>();
+    if authors.len() > MAX_LISTED_AUTHORS {
+        listed.push(format!("and {} more", authors.len() - MAX_LISTED_AUTHORS));
+    }
+    if listed.is_empty() {
+        listed.push("none".to_owned());
+    }
+    lines.push(format!("authors: {}", listed.join(", ")));
+
+    let (attachment_count, attachment_bytes) = messages
+        .iter()
+        .flat_map(|message| &message.attachments)
+        .fold((0, 0), |(count, bytes), attachment| (count + 1, bytes + attachment.size));
+    lines.push(format!(
+        "attachments: {attachment_count} ({})",
+        format_bytes(attachment_bytes)
+    ));
+
+    let too_old = messages
+        .iter()
+        .filter(|message| is_outside_bulk_delete_window(message.id))
+        .count();
+    lines.push(format!("older than 14 days (deleted one by one): {too_old}"));
+
+    lines.extend(actions.iter().cloned());
+
+    lines.join("\n")
+}
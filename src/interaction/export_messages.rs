@@ -0,0 +1,209 @@
+use crate::archive::Archive;
+use crate::attachments::format_bytes;
+use crate::interaction::move_message_and_below::parse_message_link;
+use crate::interaction::{filter, InteractionContext};
+use crate::MessageInteractError::{ArchiveTooLarge, NotInSameChannel, NotInThisServer};
+use crate::{transcript, REQUIRED_PERMISSIONS};
+use anyhow::{anyhow, Result};
+use sparkle_convenience::error::IntoError;
+use sparkle_convenience::interaction::extract::InteractionDataExt;
+use sparkle_convenience::reply::Reply;
+use std::collections::HashMap;
+use twilight_model::application::command::CommandOption;
+use twilight_model::application::command::{Command, CommandType};
+use twilight_model::application::interaction::application_command::CommandOptionValue;
+use twilight_model::channel::{Channel, Message};
+use twilight_model::http::attachment::Attachment;
+use twilight_model::id::marker::{ChannelMarker, MessageMarker};
+use twilight_model::id::Id;
+use twilight_util::builder::command::{
+    BooleanBuilder, ChannelBuilder, CommandBuilder, StringBuilder,
+};
+
+pub const CHAT_INPUT_NAME: &str = "export_messages";
+
+const FORMAT_JSON: &str = "json";
+const FORMAT_HTML: &str = "html";
+
+pub fn slash_command() -> Command {
+    let mut map = HashMap::new();
+    map.insert(
+        "fr".to_string(),
+        "Exporte les msgs d'un channel en archive JSON ou en HTML".to_string(),
+    );
+    CommandBuilder::new(
+        CHAT_INPUT_NAME,
+        "Export the messages of a channel to JSON or HTML (uses current channel if none specified)",
+        CommandType::ChatInput,
+    )
+    .default_member_permissions(REQUIRED_PERMISSIONS)
+    .dm_permission(false)
+    .option(CommandOption::from(
+        ChannelBuilder::new("channel", "Source channel (default: current channel)").required(false),
+    ))
+    .option(CommandOption::from(
+        StringBuilder::new("message_from", "Source message url, the whole channel if not set").required(false),
+    ))
+    .option(CommandOption::from(
+        StringBuilder::new("message_to", "Source message url end").required(false),
+    ))
+    .option(filter::after_option())
+    .option(filter::before_option())
+    .option(CommandOption::from(BooleanBuilder::new(
+        "attachments",
+        "bundle the attachments with the archive in a zip, or the images in the HTML transcript",
+    )))
+    .option(CommandOption::from(
+        StringBuilder::new("format", "json to import it again later (default), html to read it in a browser")
+            .choices([("json", FORMAT_JSON), ("html", FORMAT_HTML)])
+            .required(false),
+    ))
+    .description_localizations(map.iter())
+    .build()
+}
+
+impl InteractionContext<'_> {
+    pub async fn handle_export_messages_cmd(self) -> Result<()> {
+        let mut channel: Option<Id<ChannelMarker>> = None;
+        let mut link_channel: Option<Id<ChannelMarker>> = None;
+        let mut from_message: Option<Id<MessageMarker>> = None;
+        let mut to_message: Option<Id<MessageMarker>> = None;
+        let mut after: Option<i64> = None;
+        let mut before: Option<i64> = None;
+        let mut with_attachments = false;
+        let mut html = false;
+        if let Some(data) = self.interaction.data.clone() {
+            if let Some(command_data) = data.command() {
+                for option in &command_data.options {
+                    match option.name.as_str() {
+                        "channel" => {
+                            if let CommandOptionValue::Channel(id) = &option.value {
+                                channel = Some(*id);
+                            }
+                        }
+                        "message_from" | "message_to" => {
+                            if let CommandOptionValue::String(link) = &option.value {
+                                let (link_guild_id, channel_id, message_id) = parse_message_link(link)?;
+                                if Some(link_guild_id) != self.interaction.guild_id {
+                                    return Err(anyhow!(NotInThisServer));
+                                }
+                                if link_channel.is_some_and(|id| id != channel_id) {
+                                    return Err(anyhow!(NotInSameChannel));
+                                }
+                                link_channel = Some(channel_id);
+                                if option.name == "message_from" {
+                                    from_message = Some(message_id);
+                                } else {
+                                    to_message = Some(message_id);
+                                }
+                            }
+                        }
+                        filter::AFTER_OPTION_NAME => {
+                            if let CommandOptionValue::String(time) = &option.value {
+                                after = Some(filter::parse_time(time)?);
+                            }
+                        }
+                        filter::BEFORE_OPTION_NAME => {
+                            if let CommandOptionValue::String(time) = &option.value {
+                                before = Some(filter::parse_time(time)?);
+                            }
+                        }
+                        "attachments" => {
+                            if let CommandOptionValue::Boolean(b) = &option.value {
+                                with_attachments = *b;
+                            }
+                        }
+                        "format" => {
+                            if let CommandOptionValue::String(format) = &option.value {
+                                html = format == FORMAT_HTML;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let channel_id = match link_channel.or(channel) {
+            Some(channel_id) => channel_id,
+            None => self.interaction.channel.as_ref().ok()?.id,
+        };
+        let channel = self.ctx.bot.http.channel(channel_id).await?.model().await?;
+        let guild_id = self.interaction.guild_id.ok()?;
+        // The link could point anywhere the bot can read
+        if channel.guild_id != Some(guild_id) {
+            return Err(anyhow!(NotInThisServer));
+        }
+        self.ctx
+            .check_source(&channel, self.interaction.author_id().ok()?, false)
+            .await?;
+
+        self.handle.reply(Reply::new().ephemeral().update_last().content("Collecting messages...")).await?;
+        let mut messages = if from_message.is_some() || after.is_some() {
+            self.get_message_range(channel_id, from_message, to_message, (after, before))
+                .await?
+        } else {
+            self.get_all_messages_from_beginning(channel_id).await?
+        };
+        if from_message.is_none() {
+            messages.retain(|message| before.is_none_or(|before| message.timestamp.as_secs() < before));
+        }
+
+        let limit = self.ctx.guild_upload_limit(guild_id).await?;
+        let (filename, file) = self.export_file(&channel, &messages, html, with_attachments, limit).await?;
+        let size = u64::try_from(file.len())?;
+        if size > limit {
+            return Err(anyhow!(ArchiveTooLarge(format_bytes(size), format_bytes(limit))));
+        }
+
+        self.handle
+            .reply(
+                Reply::new()
+                    .ephemeral()
+                    .update_last()
+                    .content(format!("Exported {} messages from <#{channel_id}>", messages.len()))
+                    .attachment(Attachment::from_bytes(filename, file, 0)),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// The name and content of the file `messages` are exported to, which
+    /// fails when the attachments it bundles are larger than `limit`
+    async fn export_file(
+        &self,
+        channel: &Channel,
+        messages: &[Message],
+        html: bool,
+        with_attachments: bool,
+        limit: u64,
+    ) -> Result<(String, Vec<u8>)> {
+        if html {
+            // Only a lower bound, the rendered transcript is checked again
+            // with the avatars and the messages
+            let images_size = if with_attachments { transcript::images_size(messages) } else { 0 };
+            if images_size > limit {
+                return Err(anyhow!(ArchiveTooLarge(format_bytes(images_size), format_bytes(limit))));
+            }
+            self.handle.reply(Reply::new().ephemeral().update_last().content(format!("Rendering {} messages...", messages.len()))).await?;
+            let transcript = self.ctx.transcript(channel, messages, with_attachments).await?;
+            Ok((format!("export-{}.html", channel.id), transcript.into_bytes()))
+        } else if with_attachments {
+            let archive = Archive::new(channel, messages)?;
+            let attachments_size: u64 = messages
+                .iter()
+                .flat_map(|message| &message.attachments)
+                .map(|attachment| attachment.size)
+                .sum();
+            if attachments_size > limit {
+                return Err(anyhow!(ArchiveTooLarge(format_bytes(attachments_size), format_bytes(limit))));
+            }
+            self.handle.reply(Reply::new().ephemeral().update_last().content(format!("Archiving {} messages with their attachments...", messages.len()))).await?;
+            Ok((format!("export-{}.zip", channel.id), self.ctx.zip_archive(archive, messages).await?))
+        } else {
+            let archive = Archive::new(channel, messages)?;
+            Ok((format!("export-{}.json", channel.id), serde_json::to_vec_pretty(&archive)?))
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use twilight_model::application::command::CommandOption;
+use twilight_util::builder::command::{IntegerBuilder, StringBuilder};
+
+use crate::job::OnFailure;
+
+pub const ON_FAILURE_OPTION_NAME: &str = "on_failure";
+pub const RETRIES_OPTION_NAME: &str = "retries";
+
+/// How many times a message is tried again when `retries` isn't set
+const DEFAULT_RETRIES: u32 = 3;
+
+pub fn on_failure_option() -> CommandOption {
+    CommandOption::from(
+        StringBuilder::new(
+            ON_FAILURE_OPTION_NAME,
+            "what to do when a message can't be copied (default: abort)",
+        )
+        .choices([("abort", "abort"), ("skip", "skip"), ("retry", "retry")]),
+    )
+}
+
+pub fn retries_option() -> CommandOption {
+    CommandOption::from(
+        IntegerBuilder::new(
+            RETRIES_OPTION_NAME,
+            "how many times to retry a message before skipping it (default: 3)",
+        )
+        .min_value(1)
+        .max_value(10),
+    )
+}
+
+pub fn parse_on_failure(on_failure: Option<&str>, retries: Option<i64>) -> OnFailure {
+    match on_failure {
+        Some("skip") => OnFailure::Skip,
+        Some("retry") => OnFailure::Retry(
+            retries
+                .and_then(|retries| u32::try_from(retries).ok())
+                .unwrap_or(DEFAULT_RETRIES),
+        ),
+        _ => OnFailure::Abort,
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use twilight_model::application::command::CommandOption;
+use twilight_model::application::interaction::application_command::{
+    CommandDataOption, CommandOptionValue,
+};
+use twilight_model::channel::Message;
+use twilight_model::id::marker::{GuildMarker, RoleMarker, UserMarker};
+use twilight_model::id::Id;
+use twilight_model::util::Timestamp;
+use twilight_util::builder::command::{BooleanBuilder, CommandBuilder, StringBuilder};
+
+use crate::interaction::InteractionContext;
+use crate::MessageInteractError::{AfterNotBeforeBefore, InvalidDate, InvalidRegex};
+
+pub const AUTHORS_OPTION_NAME: &str = "authors";
+pub const CONTAINS_OPTION_NAME: &str = "contains";
+pub const REGEX_OPTION_NAME: &str = "regex";
+pub const HAS_ATTACHMENTS_OPTION_NAME: &str = "has_attachments";
+pub const EXCLUDE_BOTS_OPTION_NAME: &str = "exclude_bots";
+pub const EXCLUDE_PINNED_OPTION_NAME: &str = "exclude_pinned";
+pub const AFTER_OPTION_NAME: &str = "after";
+pub const BEFORE_OPTION_NAME: &str = "before";
+
+pub fn options() -> Vec<CommandOption> {
+    vec![
+        CommandOption::from(StringBuilder::new(
+            AUTHORS_OPTION_NAME,
+            "only move messages of these users or roles (mentions or ids)",
+        )),
+        CommandOption::from(StringBuilder::new(
+            CONTAINS_OPTION_NAME,
+            "only move messages containing this text, ignoring the case",
+        )),
+        CommandOption::from(StringBuilder::new(
+            REGEX_OPTION_NAME,
+            "only move messages matching this regex",
+        )),
+        CommandOption::from(BooleanBuilder::new(
+            HAS_ATTACHMENTS_OPTION_NAME,
+            "only move messages with attachments",
+        )),
+        CommandOption::from(BooleanBuilder::new(
+            EXCLUDE_BOTS_OPTION_NAME,
+            "don't move messages of bots",
+        )),
+        CommandOption::from(BooleanBuilder::new(
+            EXCLUDE_PINNED_OPTION_NAME,
+            "don't move pinned messages",
+        )),
+        after_option(),
+        before_option(),
+    ]
+}
+
+pub fn after_option() -> CommandOption {
+    CommandOption::from(StringBuilder::new(
+        AFTER_OPTION_NAME,
+        "only messages sent after this date (YYYY-MM-DD HH:MM in UTC) or duration (last 2h)",
+    ))
+}
+
+pub fn before_option() -> CommandOption {
+    CommandOption::from(StringBuilder::new(
+        BEFORE_OPTION_NAME,
+        "only messages sent before this date (YYYY-MM-DD HH:MM in UTC) or duration (2h ago)",
+    ))
+}
+
+/// Adds the filter options to `command`
+pub fn add_options(command: CommandBuilder) -> CommandBuilder {
+    options()
+        .into_iter()
+        .fold(command, CommandBuilder::option)
+}
+
+/// Which of the collected messages are moved
+#[derive(Debug, Default)]
+pub struct MessageFilter {
+    /// Ids given without a mention can be either a user or a role, they're
+    /// in both
+    users: Vec<Id<UserMarker>>,
+    roles: Vec<Id<RoleMarker>>,
+    contains: Option<String>,
+    regex: Option<Regex>,
+    has_attachments: bool,
+    exclude_bots: bool,
+    exclude_pinned: bool,
+    /// Unix timestamps in seconds
+    after: Option<i64>,
+    before: Option<i64>,
+}
+
+impl MessageFilter {
+    pub fn from_options(options: &[CommandDataOption]) -> Result<Self> {
+        let mut filter = Self::default();
+        for option in options {
+            match (option.name.as_str(), &option.value) {
+                (AUTHORS_OPTION_NAME, CommandOptionValue::String(authors)) => {
+                    filter.parse_authors(authors);
+                }
+                (CONTAINS_OPTION_NAME, CommandOptionValue::String(text)) => {
+                    filter.contains = Some(text.to_lowercase());
+                }
+                (REGEX_OPTION_NAME, CommandOptionValue::String(regex)) => {
+                    filter.regex =
+                        Some(Regex::new(regex).map_err(|_| anyhow!(InvalidRegex(regex.clone())))?);
+                }
+                (HAS_ATTACHMENTS_OPTION_NAME, CommandOptionValue::Boolean(b)) => {
+                    filter.has_attachments = *b;
+                }
+                (EXCLUDE_BOTS_OPTION_NAME, CommandOptionValue::Boolean(b)) => {
+                    filter.exclude_bots = *b;
+                }
+                (EXCLUDE_PINNED_OPTION_NAME, CommandOptionValue::Boolean(b)) => {
+                    filter.exclude_pinned = *b;
+                }
+                (AFTER_OPTION_NAME, CommandOptionValue::String(date)) => {
+                    filter.after = Some(parse_time(date)?);
+                }
+                (BEFORE_OPTION_NAME, CommandOptionValue::String(date)) => {
+                    filter.before = Some(parse_time(date)?);
+                }
+                _ => {}
+            }
+        }
+        if let (Some(after), Some(before)) = filter.window() {
+            if after >= before {
+                return Err(AfterNotBeforeBefore.into());
+            }
+        }
+
+        Ok(filter)
+    }
+
+    fn parse_authors(&mut self, authors: &str) {
+        let re = Regex::new(r"<@(!|&)?(\d+)>|(\d+)").unwrap();
+        for caps in re.captures_iter(authors) {
+            let id = caps.get(2).or_else(|| caps.get(3)).map(|id| id.as_str().parse::<u64>());
+            let Some(Ok(id)) = id else {
+                continue;
+            };
+            let Some(id) = Id::new_checked(id) else {
+                continue;
+            };
+            match caps.get(1).map(|kind| kind.as_str()) {
+                Some("&") => self.roles.push(id.cast()),
+                Some(_) => self.users.push(id),
+                None if caps.get(2).is_some() => self.users.push(id),
+                None => {
+                    self.users.push(id);
+                    self.roles.push(id.cast());
+                }
+            }
+        }
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.users.is_empty()
+            && self.roles.is_empty()
+            && self.contains.is_none()
+            && self.regex.is_none()
+            && !self.has_attachments
+            && !self.exclude_bots
+            && !self.exclude_pinned
+            && self.after.is_none()
+            && self.before.is_none()
+    }
+
+    /// The time window of the filter as Unix timestamps in seconds
+    pub const fn window(&self) -> (Option<i64>, Option<i64>) {
+        (self.after, self.before)
+    }
+
+    /// Whether `message` is moved, without looking at the roles of its
+    /// author
+    fn matches(&self, message: &Message) -> bool {
+        let sent_at = message.timestamp.as_secs();
+        let excluded = (self.has_attachments && message.attachments.is_empty())
+            || (self.exclude_bots && message.author.bot)
+            || (self.exclude_pinned && message.pinned);
+
+        !excluded
+            && self.after.is_none_or(|after| sent_at > after)
+            && self.before.is_none_or(|before| sent_at < before)
+            && self
+                .contains
+                .as_ref()
+                .is_none_or(|text| message.content.to_lowercase().contains(text))
+            && self
+                .regex
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(&message.content))
+    }
+}
+
+impl InteractionContext<'_> {
+    /// Keeps the messages `filter` lets through, the members are only looked
+    /// up when filtering by roles
+    pub async fn filter_messages(
+        &self,
+        messages: Vec<Message>,
+        filter: &MessageFilter,
+        guild_id: Id<GuildMarker>,
+    ) -> Result<Vec<Message>> {
+        if filter.is_empty() {
+            return Ok(messages);
+        }
+
+        let mut author_roles: HashMap<Id<UserMarker>, Vec<Id<RoleMarker>>> = HashMap::new();
+        let mut filtered = Vec::new();
+        for message in messages {
+            if !filter.matches(&message) {
+                continue;
+            }
+            if filter.users.is_empty() && filter.roles.is_empty() {
+                filtered.push(message);
+                continue;
+            }
+            if filter.users.contains(&message.author.id) {
+                filtered.push(message);
+                continue;
+            }
+            if filter.roles.is_empty() {
+                continue;
+            }
+
+            if let Entry::Vacant(entry) = author_roles.entry(message.author.id) {
+                // The author may have left the guild
+                let roles = match self.ctx.bot.http.guild_member(guild_id, message.author.id).await {
+                    Ok(response) => response.model().await?.roles,
+                    Err(_) => Vec::new(),
+                };
+                entry.insert(roles);
+            }
+            if author_roles[&message.author.id]
+                .iter()
+                .any(|role| filter.roles.contains(role))
+            {
+                filtered.push(message);
+            }
+        }
+
+        Ok(filtered)
+    }
+}
+
+/// Parses `YYYY-MM-DD`, `YYYY-MM-DD HH:MM` in UTC, a Discord timestamp
+/// (`<t:1696183200>`) or a duration before now (`2h`, `last 30 minutes`,
+/// `3d ago`) into a Unix timestamp in seconds, `since` in front is ignored
+pub fn parse_time(time: &str) -> Result<i64> {
+    let time = time.trim();
+    let invalid = || anyhow!(InvalidDate(time.to_owned()));
+    let lowercase = time.to_lowercase();
+    let stripped = lowercase
+        .strip_prefix("since ")
+        .or_else(|| lowercase.strip_prefix("last "))
+        .unwrap_or(&lowercase)
+        .trim();
+
+    if let Some(secs) = parse_duration(stripped.strip_suffix(" ago").unwrap_or(stripped)) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+        return i64::try_from(now.saturating_sub(secs)).map_err(|_| invalid());
+    }
+
+    // The style was lowercased with the rest
+    let discord_re = Regex::new(r"^<t:(-?\d+)(:[tdfr])?>$").unwrap();
+    if let Some(caps) = discord_re.captures(stripped) {
+        return caps[1].parse().map_err(|_| invalid());
+    }
+
+    let date_re = Regex::new(r"^(\d{4}-\d{2}-\d{2})(?:[ t](\d{2}:\d{2}))?$").unwrap();
+    let caps = date_re.captures(stripped).ok_or_else(invalid)?;
+    let clock = caps.get(2).map_or("00:00", |clock| clock.as_str());
+
+    Ok(Timestamp::parse(&format!("{}T{clock}:00+00:00", &caps[1]))
+        .map_err(|_| invalid())?
+        .as_secs())
+}
+
+/// Parses a duration like `2h`, `90 min` or `1 week` into seconds
+fn parse_duration(duration: &str) -> Option<u64> {
+    let re = Regex::new(r"^(\d+)\s*([a-z]+)$").unwrap();
+    let caps = re.captures(duration)?;
+    let unit = match &caps[2] {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => 24 * 60 * 60,
+        "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    caps[1].parse::<u64>().ok()?.checked_mul(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use twilight_model::application::interaction::application_command::{
+        CommandDataOption, CommandOptionValue,
+    };
+
+    use super::{parse_duration, parse_time, MessageFilter, AFTER_OPTION_NAME, BEFORE_OPTION_NAME};
+
+    fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            .try_into()
+            .unwrap()
+    }
+
+    fn option(name: &str, value: &str) -> CommandDataOption {
+        CommandDataOption {
+            name: name.to_owned(),
+            value: CommandOptionValue::String(value.to_owned()),
+        }
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("30s"), Some(30));
+        assert_eq!(parse_duration("90 min"), Some(90 * 60));
+        assert_eq!(parse_duration("2h"), Some(2 * 60 * 60));
+        assert_eq!(parse_duration("3 days"), Some(3 * 24 * 60 * 60));
+        assert_eq!(parse_duration("1 week"), Some(7 * 24 * 60 * 60));
+        assert_eq!(parse_duration("2 fortnights"), None);
+        assert_eq!(parse_duration("h"), None);
+    }
+
+    #[test]
+    fn parse_duration_rejects_negative_and_overflowing() {
+        assert_eq!(parse_duration("-2h"), None);
+        assert_eq!(parse_duration("18446744073709551615w"), None);
+        assert_eq!(parse_duration("99999999999999999999s"), None);
+    }
+
+    #[test]
+    fn parse_time_dates() {
+        assert_eq!(parse_time("2023-10-01").unwrap(), 1_696_118_400);
+        assert_eq!(parse_time("2023-10-01 17:20").unwrap(), 1_696_180_800);
+        assert_eq!(parse_time("since 2023-10-01").unwrap(), 1_696_118_400);
+        assert_eq!(parse_time("<t:1696183200:R>").unwrap(), 1_696_183_200);
+        assert_eq!(parse_time("<t:-60>").unwrap(), -60);
+    }
+
+    #[test]
+    fn parse_time_durations() {
+        let durations = [
+            ("2h", 2 * 60 * 60),
+            ("last 30 minutes", 30 * 60),
+            ("3d ago", 3 * 24 * 60 * 60),
+        ];
+        for (time, secs) in durations {
+            let parsed = parse_time(time).unwrap();
+            // The clock can tick between the two calls
+            assert!((now() - secs - parsed).abs() <= 1, "{time}");
+        }
+    }
+
+    #[test]
+    fn parse_time_rejects_invalid() {
+        for time in ["", "yesterday", "-2h", "2023-13-45", "2023-10-01 25:00", "99999999999999999999h"] {
+            assert!(parse_time(time).is_err(), "{time}");
+        }
+    }
+
+    #[test]
+    fn from_options_reads_the_window() {
+        let filter = MessageFilter::from_options(&[
+            option(AFTER_OPTION_NAME, "2023-10-01"),
+            option(BEFORE_OPTION_NAME, "2023-10-02"),
+        ])
+        .unwrap();
+
+        assert_eq!(filter.window(), (Some(1_696_118_400), Some(1_696_204_800)));
+        assert!(!filter.is_empty());
+    }
+
+    #[test]
+    fn from_options_rejects_after_not_before_before() {
+        for (after, before) in [("2023-10-02", "2023-10-01"), ("2023-10-01", "2023-10-01")] {
+            assert!(MessageFilter::from_options(&[
+                option(AFTER_OPTION_NAME, after),
+                option(BEFORE_OPTION_NAME, before),
+            ])
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn from_options_rejects_invalid_values() {
+        assert!(MessageFilter::from_options(&[option(AFTER_OPTION_NAME, "soon")]).is_err());
+        assert!(MessageFilter::from_options(&[option(super::REGEX_OPTION_NAME, "(")]).is_err());
+        assert!(MessageFilter::from_options(&[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,30 @@
+use twilight_model::application::command::CommandOption;
+use twilight_util::builder::command::StringBuilder;
+
+pub const POST_TITLE_OPTION_NAME: &str = "post_title";
+pub const POST_TAGS_OPTION_NAME: &str = "post_tags";
+
+pub fn post_title_option() -> CommandOption {
+    CommandOption::from(
+        StringBuilder::new(
+            POST_TITLE_OPTION_NAME,
+            "title of the post when moving to a forum (default: the first message)",
+        )
+        .max_length(100),
+    )
+}
+
+pub fn post_tags_option() -> CommandOption {
+    CommandOption::from(StringBuilder::new(
+        POST_TAGS_OPTION_NAME,
+        "comma separated tags of the post when moving to a forum",
+    ))
+}
+
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
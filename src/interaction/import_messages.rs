@@ -0,0 +1,253 @@
+use crate::archive::{self, ArchivedAttachment, ArchivedMessage};
+use crate::attachments::format_bytes;
+use crate::interaction::InteractionContext;
+use crate::job::{FailedMessage, MoveOutcome};
+use crate::message::{truncate_thread_name, MovedCopies};
+use crate::REQUIRED_PERMISSIONS;
+use anyhow::{anyhow, Result};
+use sparkle_convenience::error::IntoError;
+use sparkle_convenience::interaction::extract::InteractionDataExt;
+use sparkle_convenience::reply::Reply;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+use twilight_model::application::command::CommandOption;
+use twilight_model::application::command::{Command, CommandType};
+use twilight_model::application::interaction::application_command::CommandOptionValue;
+use twilight_model::channel::message::MessageReference;
+use twilight_model::channel::{Channel, ChannelType, Message};
+use twilight_model::http::attachment::Attachment;
+use twilight_model::id::marker::{ChannelMarker, MessageMarker};
+use twilight_model::id::Id;
+use twilight_util::builder::command::{AttachmentBuilder, ChannelBuilder, CommandBuilder};
+
+pub const CHAT_INPUT_NAME: &str = "import_messages";
+
+/// The name of the post created when importing to a forum an archive
+/// without a channel name
+const DEFAULT_POST_TITLE: &str = "imported messages";
+/// How often the progress is shown
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(4);
+
+pub fn slash_command() -> Command {
+    let mut map = HashMap::new();
+    map.insert(
+        "fr".to_string(),
+        "Renvoie les msgs d'une archive JSON dans un channel".to_string(),
+    );
+    CommandBuilder::new(
+        CHAT_INPUT_NAME,
+        "Send the messages of an /export_messages archive again (default: current channel)",
+        CommandType::ChatInput,
+    )
+    .default_member_permissions(REQUIRED_PERMISSIONS)
+    .dm_permission(false)
+    .option(CommandOption::from(
+        AttachmentBuilder::new("archive", "the .json or .zip file made by /export_messages").required(true),
+    ))
+    .option(CommandOption::from(
+        ChannelBuilder::new("channel", "the channel or thread to send the messages to").required(false),
+    ))
+    .description_localizations(map.iter())
+    .build()
+}
+
+impl InteractionContext<'_> {
+    pub async fn handle_import_messages_cmd(self) -> Result<()> {
+        let mut archive_url: Option<String> = None;
+        let mut channel: Option<Id<ChannelMarker>> = None;
+        if let Some(data) = self.interaction.data.clone() {
+            if let Some(command_data) = data.command() {
+                for option in &command_data.options {
+                    match option.name.as_str() {
+                        "archive" => {
+                            if let CommandOptionValue::Attachment(id) = &option.value {
+                                archive_url = command_data
+                                    .resolved
+                                    .as_ref()
+                                    .and_then(|resolved| resolved.attachments.get(id))
+                                    .map(|attachment| attachment.url.clone());
+                            }
+                        }
+                        "channel" => {
+                            if let CommandOptionValue::Channel(id) = &option.value {
+                                channel = Some(*id);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let archive_url = archive_url.ok_or_else(|| anyhow!("Missing parameters"))?;
+        let channel_id = match channel {
+            Some(channel_id) => channel_id,
+            None => self.interaction.channel.as_ref().ok()?.id,
+        };
+        let mut target = self.ctx.bot.http.channel(channel_id).await?.model().await?;
+        let guild_id = self.interaction.guild_id.ok()?;
+        let user_id = self.interaction.author_id().ok()?;
+        self.ctx.check_target(&target, user_id).await?;
+
+        self.handle.reply(Reply::new().ephemeral().update_last().content("Reading the archive...")).await?;
+        let bytes = reqwest::get(&archive_url)
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let (archive, mut files) = archive::read(bytes.to_vec())?;
+        let limit = self.ctx.guild_upload_limit(guild_id).await?;
+
+        self.start_move_record();
+        let mut moved = MovedCopies::new();
+        let mut sent: HashMap<Id<MessageMarker>, Message> = HashMap::new();
+        let mut outcome = MoveOutcome::default();
+        let total = archive.messages.len();
+        let mut last_update = Instant::now();
+        for (idx, archived) in archive.messages.iter().enumerate() {
+            if idx == 0 || last_update.elapsed() >= PROGRESS_INTERVAL {
+                self.show_progress(idx, total).await?;
+                last_update = Instant::now();
+            }
+
+            let post_title = (target.kind == ChannelType::GuildForum).then(|| {
+                truncate_thread_name(archive.channel_name.as_deref().unwrap_or(DEFAULT_POST_TITLE))
+            });
+            let (message, copy) = match Box::pin(self.import_message(
+                archived,
+                &target,
+                post_title.as_deref(),
+                &mut files,
+                limit,
+                &sent,
+                &moved,
+            ))
+            .await
+            {
+                Ok(imported) => imported,
+                Err(err) => {
+                    outcome.failed.push(FailedMessage {
+                        message_id: archived.id,
+                        reason: err.to_string(),
+                    });
+                    continue;
+                }
+            };
+            // The next messages go to the post the first one created
+            if target.kind == ChannelType::GuildForum {
+                target = self.ctx.bot.http.channel(copy.channel_id).await?.model().await?;
+            }
+
+            moved.insert(archived.id, (&copy).into());
+            self.record_copy(&message, std::slice::from_ref(&copy));
+            outcome.copied.push(archived.id);
+            sent.insert(
+                archived.id,
+                Message {
+                    reference: None,
+                    referenced_message: None,
+                    ..message
+                },
+            );
+        }
+
+        self.reply_move_report(guild_id, &[], &outcome).await
+    }
+
+    /// Sends `archived` to `target`, returning the message that was sent and
+    /// its copy. A reply to a message in `sent` quotes it.
+    #[allow(clippy::too_many_arguments)]
+    async fn import_message(
+        &self,
+        archived: &ArchivedMessage,
+        target: &Channel,
+        post_title: Option<&str>,
+        files: &mut HashMap<String, Vec<u8>>,
+        limit: u64,
+        sent: &HashMap<Id<MessageMarker>, Message>,
+        moved: &MovedCopies,
+    ) -> Result<(Message, Message)> {
+        let mut message = archived.to_message(target.id, target.guild_id);
+        // Only replies to messages of the archive can point to something
+        if let Some(replied) = archived.reference.and_then(|id| sent.get(&id)) {
+            message.reference = Some(MessageReference {
+                channel_id: Some(replied.channel_id),
+                guild_id: target.guild_id,
+                message_id: Some(replied.id),
+                fail_if_not_exists: None,
+            });
+            message.referenced_message = Some(Box::new(replied.clone()));
+        }
+        let (attachments, notes) = self.import_attachments(&archived.attachments, files, limit).await;
+        if !notes.is_empty() {
+            if !message.content.is_empty() {
+                message.content.push('\n');
+            }
+            message.content.push_str(&notes.join("\n"));
+        }
+        if message.content.is_empty() && message.embeds.is_empty() && attachments.is_empty() {
+            return Err(anyhow!("there's nothing in it that can be sent"));
+        }
+
+        let copy = Box::pin(self.ctx.execute_webhook_as_member(
+            &message,
+            target,
+            &attachments,
+            post_title,
+            moved,
+        ))
+        .await?;
+
+        Ok((message, copy))
+    }
+
+    /// The files of `attachments`, from the zip when they were bundled or
+    /// downloaded again otherwise, with a note for each one that couldn't be
+    /// or doesn't fit in `limit`
+    async fn import_attachments(
+        &self,
+        attachments: &[ArchivedAttachment],
+        files: &mut HashMap<String, Vec<u8>>,
+        limit: u64,
+    ) -> (Vec<Attachment>, Vec<String>) {
+        let mut http_attachments = Vec::new();
+        let mut notes = Vec::new();
+        let mut size = 0;
+
+        for archived in attachments {
+            if size + archived.size > limit {
+                notes.push(format!(
+                    "*{} ({}) was too large to be restored*",
+                    archived.filename,
+                    format_bytes(archived.size)
+                ));
+                continue;
+            }
+            let file = match archived.file.as_ref().and_then(|path| files.remove(path)) {
+                Some(file) => Some(file),
+                None => download(&archived.url).await,
+            };
+            let Some(file) = file else {
+                notes.push(format!("*{} couldn't be restored*", archived.filename));
+                continue;
+            };
+
+            size += archived.size;
+            let mut http_attachment =
+                Attachment::from_bytes(archived.filename.clone(), file, archived.id.get());
+            if let Some(description) = &archived.description {
+                http_attachment.description(description.clone());
+            }
+            http_attachments.push(http_attachment);
+        }
+
+        (http_attachments, notes)
+    }
+}
+
+/// The file at `url`, the links of deleted messages stop working
+async fn download(url: &str) -> Option<Vec<u8>> {
+    let response = reqwest::get(url).await.ok()?.error_for_status().ok()?;
+    Some(response.bytes().await.ok()?.to_vec())
+}
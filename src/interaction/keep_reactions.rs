@@ -0,0 +1,30 @@
+use twilight_model::application::command::CommandOption;
+use twilight_util::builder::command::StringBuilder;
+
+use crate::job::KeepReactions;
+
+pub const OPTION_NAME: &str = "reactions";
+
+pub fn option() -> CommandOption {
+    CommandOption::from(
+        StringBuilder::new(
+            OPTION_NAME,
+            "what to do with the reactions of the messages (default: drop)",
+        )
+        .choices([
+            ("drop", "drop"),
+            ("summary", "summary"),
+            ("summary with users", "summary_with_users"),
+            ("react as the bot", "react"),
+        ]),
+    )
+}
+
+pub fn parse(reactions: &str) -> KeepReactions {
+    match reactions {
+        "summary" => KeepReactions::Summary,
+        "summary_with_users" => KeepReactions::SummaryWithUsers,
+        "react" => KeepReactions::React,
+        _ => KeepReactions::Drop,
+    }
+}
@@ -0,0 +1,28 @@
+use twilight_model::application::command::CommandOption;
+use twilight_util::builder::command::StringBuilder;
+
+use crate::attachments::LargeAttachments;
+
+pub const OPTION_NAME: &str = "large_attachments";
+
+pub fn option() -> CommandOption {
+    CommandOption::from(
+        StringBuilder::new(
+            OPTION_NAME,
+            "what to do with the attachments too large for the server (default: link)",
+        )
+        .choices([
+            ("link to the original", "link"),
+            ("split over several messages", "split"),
+            ("skip", "skip"),
+        ]),
+    )
+}
+
+pub fn parse(large_attachments: &str) -> LargeAttachments {
+    match large_attachments {
+        "split" => LargeAttachments::Split,
+        "skip" => LargeAttachments::Skip,
+        _ => LargeAttachments::Link,
+    }
+}
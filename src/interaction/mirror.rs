@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use sparkle_convenience::error::IntoError;
+use sparkle_convenience::interaction::extract::InteractionDataExt;
+use sparkle_convenience::reply::Reply;
+use twilight_model::application::command::{Command, CommandOption, CommandType};
+use twilight_model::application::interaction::application_command::{CommandDataOption, CommandOptionValue};
+use twilight_model::channel::ChannelType;
+use twilight_model::id::marker::ChannelMarker;
+use twilight_model::id::Id;
+use twilight_util::builder::command::{BooleanBuilder, ChannelBuilder, CommandBuilder, SubCommandBuilder};
+
+use crate::interaction::InteractionContext;
+use crate::mirror::{webhook_channel, Mirror};
+use crate::MessageInteractError::{ForumNotPost, MirrorExists, NoMirror, SameValueForId};
+use crate::{CustomError, REQUIRED_PERMISSIONS};
+
+pub const CHAT_INPUT_NAME: &str = "mirror";
+
+const START_NAME: &str = "start";
+const STOP_NAME: &str = "stop";
+const LIST_NAME: &str = "list";
+
+pub fn slash_command() -> Command {
+    let mut map = HashMap::new();
+    map.insert(
+        "fr".to_string(),
+        "Copie les nouveaux msgs d'un channel dans un autre".to_string(),
+    );
+    CommandBuilder::new(
+        CHAT_INPUT_NAME,
+        "Copy the new messages of a channel to another as they're sent",
+        CommandType::ChatInput,
+    )
+    .default_member_permissions(REQUIRED_PERMISSIONS)
+    .dm_permission(false)
+    .option(CommandOption::from(
+        SubCommandBuilder::new(START_NAME, "start copying the new messages of a channel to another")
+            .option(CommandOption::from(
+                ChannelBuilder::new("source", "the channel to copy the new messages of").required(true),
+            ))
+            .option(CommandOption::from(
+                ChannelBuilder::new("target", "the channel or thread to copy them to").required(true),
+            ))
+            .option(CommandOption::from(BooleanBuilder::new(
+                "edits",
+                "also edit the copies when the messages are edited",
+            )))
+            .option(CommandOption::from(BooleanBuilder::new(
+                "deletes",
+                "also delete the copies when the messages are deleted",
+            ))),
+    ))
+    .option(CommandOption::from(
+        SubCommandBuilder::new(STOP_NAME, "stop copying the new messages of a channel")
+            .option(CommandOption::from(
+                ChannelBuilder::new("source", "the channel to stop copying the messages of").required(true),
+            ))
+            .option(CommandOption::from(
+                ChannelBuilder::new("target", "only stop copying them to this channel").required(false),
+            )),
+    ))
+    .option(CommandOption::from(SubCommandBuilder::new(
+        LIST_NAME,
+        "show the channels mirrored in this server",
+    )))
+    .description_localizations(map.iter())
+    .build()
+}
+
+impl InteractionContext<'_> {
+    pub async fn handle_mirror_cmd(self) -> Result<()> {
+        let mut subcommand: Option<(String, Vec<CommandDataOption>)> = None;
+        if let Some(data) = self.interaction.data.clone() {
+            if let Some(command_data) = data.command() {
+                for option in &command_data.options {
+                    if let CommandOptionValue::SubCommand(options) = &option.value {
+                        subcommand = Some((option.name.clone(), options.clone()));
+                    }
+                }
+            }
+        }
+
+        let (name, options) = subcommand.ok_or_else(|| anyhow!("Missing parameters"))?;
+        let mut source: Option<Id<ChannelMarker>> = None;
+        let mut target: Option<Id<ChannelMarker>> = None;
+        let mut forward_edits = false;
+        let mut forward_deletes = false;
+        for option in options {
+            match (option.name.as_str(), option.value) {
+                ("source", CommandOptionValue::Channel(id)) => source = Some(id),
+                ("target", CommandOptionValue::Channel(id)) => target = Some(id),
+                ("edits", CommandOptionValue::Boolean(value)) => forward_edits = value,
+                ("deletes", CommandOptionValue::Boolean(value)) => forward_deletes = value,
+                _ => {}
+            }
+        }
+
+        match name.as_str() {
+            START_NAME => {
+                let source = source.ok_or_else(|| anyhow!("Missing parameters"))?;
+                let target = target.ok_or_else(|| anyhow!("Missing parameters"))?;
+                self.start_mirror(source, target, forward_edits, forward_deletes).await
+            }
+            STOP_NAME => {
+                let source = source.ok_or_else(|| anyhow!("Missing parameters"))?;
+                self.stop_mirror(source, target).await
+            }
+            _ => self.list_mirrors().await,
+        }
+    }
+
+    async fn start_mirror(
+        &self,
+        source: Id<ChannelMarker>,
+        target: Id<ChannelMarker>,
+        forward_edits: bool,
+        forward_deletes: bool,
+    ) -> Result<()> {
+        if !self.ctx.mirrors.enabled {
+            return Err(CustomError::MirrorsDisabled.into());
+        }
+        if source == target {
+            return Err(SameValueForId.into());
+        }
+        let user_id = self.interaction.author_id().ok()?;
+        let source_channel = self.ctx.bot.http.channel(source).await?.model().await?;
+        let target_channel = self.ctx.bot.http.channel(target).await?.model().await?;
+        // Every message would need a post of its own
+        if source_channel.kind == ChannelType::GuildForum || target_channel.kind == ChannelType::GuildForum {
+            return Err(ForumNotPost.into());
+        }
+        self.ctx.check_source(&source_channel, user_id, false).await?;
+        self.ctx.check_target(&target_channel, user_id).await?;
+        let webhook = self
+            .ctx
+            .channel_webhook(webhook_channel(&target_channel)?)
+            .await?;
+
+        let added = self
+            .ctx
+            .mirrors
+            .add(Mirror {
+                guild_id: self.interaction.guild_id.ok()?,
+                source_channel: source,
+                target_channel: target,
+                forward_edits,
+                forward_deletes,
+                webhook_id: Some(webhook.id),
+            })
+            .await?;
+        if !added {
+            return Err(MirrorExists.into());
+        }
+
+        self.handle
+            .reply(Reply::new().ephemeral().update_last().content(format!(
+                "The new messages of <#{source}> will be copied to <#{target}> as they're sent"
+            )))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn stop_mirror(&self, source: Id<ChannelMarker>, target: Option<Id<ChannelMarker>>) -> Result<()> {
+        let removed = self.ctx.mirrors.remove(source, target).await?;
+        if removed.is_empty() {
+            return Err(NoMirror.into());
+        }
+
+        let targets = removed
+            .iter()
+            .map(|mirror| format!("<#{}>", mirror.target_channel))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.handle
+            .reply(Reply::new().ephemeral().update_last().content(format!(
+                "The new messages of <#{source}> aren't copied to {targets} anymore"
+            )))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_mirrors(&self) -> Result<()> {
+        let mirrors = self.ctx.mirrors.in_guild(self.interaction.guild_id.ok()?);
+        let content = if mirrors.is_empty() {
+            "No channel is mirrored in this server".to_owned()
+        } else {
+            mirrors
+                .iter()
+                .map(|mirror| {
+                    let forwarded = match (mirror.forward_edits, mirror.forward_deletes) {
+                        (true, true) => " (with edits and deletes)",
+                        (true, false) => " (with edits)",
+                        (false, true) => " (with deletes)",
+                        (false, false) => "",
+                    };
+                    format!("<#{}> → <#{}>{forwarded}", mirror.source_channel, mirror.target_channel)
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        self.handle
+            .reply(Reply::new().ephemeral().update_last().content(content))
+            .await?;
+
+        Ok(())
+    }
+}
@@ -7,14 +7,12 @@ use sparkle_convenience::{
 use twilight_model::{
     application::interaction::Interaction,
     channel::{Channel, ChannelType},
-    guild::Permissions,
     id::{marker::ChannelMarker, Id},
 };
-use twilight_util::permission_calculator::PermissionCalculator;
 
 use crate::{
     interaction::{channel_select_menu::ChannelSelectMenu, InteractionContext},
-    Context, CustomError,
+    Context,
 };
 
 pub const CUSTOM_ID: &str = "move_channel";
@@ -33,6 +31,7 @@ impl<'a> InteractionContext<'a> {
                     vec![
                         ChannelType::GuildText,
                         ChannelType::GuildAnnouncement,
+                        ChannelType::GuildForum,
                         ChannelType::AnnouncementThread,
                         ChannelType::PublicThread,
                         ChannelType::PrivateThread,
@@ -75,63 +74,11 @@ impl Context {
             .next()
             .ok()?
             .parse::<Id<ChannelMarker>>()?;
-        let member = interaction.member.as_ref().ok()?;
+        let user_id = interaction.author_id().ok()?;
 
-        let guild = self
-            .bot
-            .http
-            .guild(interaction.guild_id.ok()?)
-            .await?
-            .model()
-            .await?;
         let channel = self.bot.http.channel(channel_id).await?.model().await?;
-        let permission_overwrites = if channel.kind.is_thread() {
-            self.bot
-                .http
-                .channel(channel.parent_id.ok()?)
-                .await?
-                .model()
-                .await?
-                .permission_overwrites
-                .ok()?
-        } else {
-            channel.permission_overwrites.clone().ok()?
-        };
-
-        let everyone_role = guild
-            .roles
-            .iter()
-            .find_map(|role| (role.id.cast() == guild.id).then_some(role.permissions))
-            .ok()?;
-
-        let member_roles = guild
-            .roles
-            .iter()
-            .filter_map(|role| {
-                member
-                    .roles
-                    .contains(&role.id)
-                    .then_some((role.id, role.permissions))
-            })
-            .collect::<Vec<_>>();
-
-        let permissions = PermissionCalculator::new(
-            guild.id,
-            member.user.as_ref().ok()?.id,
-            everyone_role,
-            &member_roles,
-        )
-        .in_channel(channel.kind, &permission_overwrites);
+        self.check_target(&channel, user_id).await?;
 
-        let required_permissions = if channel.kind.is_thread() {
-            Permissions::SEND_MESSAGES_IN_THREADS
-        } else {
-            Permissions::SEND_MESSAGES
-        };
-        if !permissions.contains(required_permissions) {
-            return Err(CustomError::SendMessagesPermissionMissing.into());
-        }
-        
         Ok(channel)
     }
 }
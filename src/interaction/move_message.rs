@@ -85,7 +85,7 @@ impl InteractionContext<'_> {
         let message = self.handle_message_command()?;
         message::check(&message)?;
         let channel = self.wait_for_channel_select_interaction().await?;
-        self.move_message(message, channel, true).await?;
+        Box::pin(self.move_message(message, channel, true)).await?;
         Ok(())
     }
     pub async fn handle_command_call(self) -> Result<()> {
@@ -145,7 +145,7 @@ impl InteractionContext<'_> {
             .model()
             .await?;
 
-        self.move_message(message, r_channel, remove.unwrap_or(false)).await?;
+        Box::pin(self.move_message(message, r_channel, remove.unwrap_or(false))).await?;
         Ok(())
     }
 
@@ -1,7 +1,18 @@
 use std::collections::HashMap;
 use twilight_model::application::interaction::application_command::CommandOptionValue;
 
-use crate::{interaction::InteractionContext, REQUIRED_PERMISSIONS};
+use crate::{
+    interaction::{
+        dry_run, failure,
+        filter::{self, MessageFilter},
+        forum, keep_reactions, large_attachments,
+        move_options::MoveCommandOptions,
+        utils::copied_only,
+        InteractionContext,
+    },
+    job::{DeleteOld, MoveOptions},
+    REQUIRED_PERMISSIONS,
+};
 use anyhow::{anyhow, Result};
 use regex::Regex;
 use sparkle_convenience::interaction::extract::InteractionDataExt;
@@ -32,7 +43,7 @@ pub fn slash_command() -> Command {
         "fr".to_string(),
         "Déplace tout les messages à partir du message jusqu'au channel correspondant".to_string(),
     );
-    CommandBuilder::new(
+    let command = CommandBuilder::new(
         CHAT_INPUT_NAME,
         "Move all the messages below the choosen one to the corresponding channel",
         CommandType::ChatInput,
@@ -48,11 +59,19 @@ pub fn slash_command() -> Command {
         "delete_old",
         "delete the old messages",
     )))
-    .default_member_permissions(REQUIRED_PERMISSIONS)
-    .description_localizations(map.iter())
-    .validate()
-    .unwrap()
-    .build()
+    .option(dry_run::option())
+    .option(forum::post_title_option())
+    .option(forum::post_tags_option())
+    .option(failure::on_failure_option())
+    .option(failure::retries_option())
+    .option(keep_reactions::option())
+    .option(large_attachments::option())
+    .default_member_permissions(REQUIRED_PERMISSIONS);
+    filter::add_options(command)
+        .description_localizations(map.iter())
+        .validate()
+        .unwrap()
+        .build()
 }
 
 pub fn slash_command2() -> Command {
@@ -61,7 +80,7 @@ pub fn slash_command2() -> Command {
         "fr".to_string(),
         "Déplace tout les messages à partir du message jusqu'au channel correspondant".to_string(),
     );
-    CommandBuilder::new(
+    let command = CommandBuilder::new(
         CHAT_INPUT_NAME_2,
         "Move all the messages below the choosen one to the corresponding channel",
         CommandType::ChatInput,
@@ -78,10 +97,18 @@ pub fn slash_command2() -> Command {
         "delete_old",
         "delete the old messages",
     )))
-    .description_localizations(map.iter())
-    .validate()
-    .unwrap()
-    .build()
+    .option(dry_run::option())
+    .option(forum::post_title_option())
+    .option(forum::post_tags_option())
+    .option(failure::on_failure_option())
+    .option(failure::retries_option())
+    .option(keep_reactions::option())
+    .option(large_attachments::option());
+    filter::add_options(command)
+        .description_localizations(map.iter())
+        .validate()
+        .unwrap()
+        .build()
 }
 pub fn parse_message_link(
     link: &str,
@@ -105,18 +132,27 @@ impl InteractionContext<'_> {
         self.handle.reply(Reply::new().ephemeral().update_last().content("moving messages...")).await?;
         let messages = self.get_message_borned(message.channel_id, message.id, None).await?;
         self.handle.reply(Reply::new().ephemeral().update_last().content(format!("Moving {} messages", messages.len()))).await?;
-        self.move_messages(&messages, &channel, guild_id, None).await?;
-        
-        self.bulk_delete(messages, Some(guild_id)).await?;
-        Ok(())
+        let options = MoveOptions {
+            delete_old: DeleteOld::Messages,
+            ..MoveOptions::default()
+        };
+        let outcome = self.move_messages(&messages, &channel, guild_id, &options).await?;
+
+        self.bulk_delete(copied_only(&messages, &outcome), Some(guild_id))
+            .await?;
+        self.reply_move_report(guild_id, &messages, &outcome).await
     }
     pub async fn handle_move_and_below_command_call(self) -> Result<()> {
         let mut message_id: Option<String> = None;
         let mut result_channel: Option<Id<ChannelMarker>> = None;
         let mut remove: Option<bool> = None;
+        let mut move_options = MoveCommandOptions::default();
+        let mut filter = MessageFilter::default();
         let mut i_channel: Option<Channel> = None;
         if let Some(data) = self.interaction.data.clone() {
             if let Some(command_data) = data.command() {
+                move_options = MoveCommandOptions::from_options(&command_data.options);
+                filter = MessageFilter::from_options(&command_data.options)?;
                 for option in &command_data.options {
                     match option.name.as_str() {
                         "message_id" => {
@@ -158,15 +194,12 @@ impl InteractionContext<'_> {
                 .parse()
                 .map_err(|_| anyhow!("Invalid message ID format"))?,
         );
-        if i_channel.is_none() {
-            return Err(anyhow!("Missing input channel"));
-        }
-        // Rest of your code remains the same
+        let i_channel = i_channel.ok_or_else(|| anyhow!("Missing input channel"))?;
         let message = self
             .ctx
             .bot
             .http
-            .message(i_channel.unwrap().id, message_id_num)
+            .message(i_channel.id, message_id_num)
             .await?
             .model()
             .await?;
@@ -180,20 +213,37 @@ impl InteractionContext<'_> {
             .model()
             .await?;
 
-        let guild_id = r_channel
-            .clone()
-            .guild_id
-            .ok_or(anyhow!("Message not in a guild"))?;
+        let guild_id = self.interaction.guild_id.ok()?;
+        // The link can point to a channel of another guild, Discord didn't
+        // check the user's permissions there
+        let source_guild_id = i_channel.guild_id.ok_or_else(|| anyhow!("Message not in a guild"))?;
+        if source_guild_id != guild_id {
+            self.ctx
+                .check_source(&i_channel, self.interaction.author_id().ok()?, remove.unwrap_or(false))
+                .await?;
+        }
         self.handle.reply(Reply::new().ephemeral().update_last().content("moving messages...")).await?;
         let messages = self.get_message_borned(message.channel_id, message.id, None).await?;
+        let messages = self.filter_messages(messages, &filter, source_guild_id).await?;
+        let MoveCommandOptions { dry_run, mut options } = move_options;
+        if dry_run {
+            let mut actions = vec![format!("would be moved to: <#{}>", r_channel.id)];
+            if remove.unwrap_or(false) {
+                actions.push("the original messages would be deleted".to_owned());
+            }
+            return self.reply_dry_run(&messages, Some(source_guild_id), &actions).await;
+        }
         self.handle.reply(Reply::new().ephemeral().update_last().content(format!("Moving {} messages", messages.len()))).await?;
-        self.move_messages(&messages, &r_channel, guild_id, None).await?;
         if remove.unwrap_or(false) {
-            self.bulk_delete(messages, Some(guild_id)).await?;
+            options.delete_old = DeleteOld::Messages;
         }
-        self.handle.reply(Reply::new().ephemeral().update_last().content("Done")).await?;
-        
-        Ok(())
+        let outcome = self.move_messages(&messages, &r_channel, guild_id, &options).await?;
+        if remove.unwrap_or(false) {
+            self.bulk_delete(copied_only(&messages, &outcome), Some(source_guild_id))
+                .await?;
+        }
+
+        self.reply_move_report(source_guild_id, &messages, &outcome).await
     }
 
 }
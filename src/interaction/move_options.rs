@@ -0,0 +1,51 @@
+use twilight_model::application::interaction::application_command::{
+    CommandDataOption, CommandOptionValue,
+};
+
+use crate::interaction::{dry_run, failure, forum, keep_reactions, large_attachments};
+use crate::job::MoveOptions;
+
+/// The options the move commands share, the ones that aren't part of the
+/// command are left as they are by default
+#[derive(Debug, Default)]
+pub struct MoveCommandOptions {
+    pub dry_run: bool,
+    pub options: MoveOptions,
+}
+
+impl MoveCommandOptions {
+    pub fn from_options(options: &[CommandDataOption]) -> Self {
+        let mut parsed = Self::default();
+        let mut on_failure = None;
+        let mut retries = None;
+        for option in options {
+            match (option.name.as_str(), &option.value) {
+                (dry_run::OPTION_NAME, CommandOptionValue::Boolean(b)) => {
+                    parsed.dry_run = *b;
+                }
+                (forum::POST_TITLE_OPTION_NAME, CommandOptionValue::String(title)) => {
+                    parsed.options.post_title = Some(title.clone());
+                }
+                (forum::POST_TAGS_OPTION_NAME, CommandOptionValue::String(tags)) => {
+                    parsed.options.post_tags = forum::parse_tags(tags);
+                }
+                (failure::ON_FAILURE_OPTION_NAME, CommandOptionValue::String(policy)) => {
+                    on_failure = Some(policy.as_str());
+                }
+                (failure::RETRIES_OPTION_NAME, CommandOptionValue::Integer(count)) => {
+                    retries = Some(*count);
+                }
+                (keep_reactions::OPTION_NAME, CommandOptionValue::String(reactions)) => {
+                    parsed.options.reactions = keep_reactions::parse(reactions);
+                }
+                (large_attachments::OPTION_NAME, CommandOptionValue::String(choice)) => {
+                    parsed.options.large_attachments = Some(large_attachments::parse(choice));
+                }
+                _ => {}
+            }
+        }
+        parsed.options.on_failure = failure::parse_on_failure(on_failure, retries);
+
+        parsed
+    }
+}
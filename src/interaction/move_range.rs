@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use sparkle_convenience::error::IntoError;
+use sparkle_convenience::reply::Reply;
+use tokio::time::Instant;
+use twilight_model::application::command::{Command, CommandType};
+use twilight_model::id::marker::{ChannelMarker, MessageMarker};
+use twilight_model::id::Id;
+use twilight_util::builder::command::CommandBuilder;
+
+use crate::interaction::utils::copied_only;
+use crate::interaction::InteractionContext;
+use crate::job::{DeleteOld, MoveOptions};
+use crate::MessageInteractError::NotInSameChannel;
+use crate::{CustomError, REQUIRED_PERMISSIONS};
+
+pub const MARK_START_NAME: &str = "mark range start";
+pub const MOVE_RANGE_NAME: &str = "move range ending here";
+
+/// How long a marked start can still be used to end a range.
+pub const RANGE_START_EXPIRY: Duration = Duration::from_mins(10);
+
+pub fn mark_start_command() -> Command {
+    CommandBuilder::new(MARK_START_NAME, "", CommandType::Message)
+        .dm_permission(false)
+        .default_member_permissions(REQUIRED_PERMISSIONS)
+        .build()
+}
+
+pub fn move_range_command() -> Command {
+    CommandBuilder::new(MOVE_RANGE_NAME, "", CommandType::Message)
+        .dm_permission(false)
+        .default_member_permissions(REQUIRED_PERMISSIONS)
+        .build()
+}
+
+/// The first message of a range marked by a user.
+pub struct RangeStart {
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+    marked_at: Instant,
+}
+
+impl InteractionContext<'_> {
+    pub async fn handle_mark_range_start_command(self) -> Result<()> {
+        let message = self.handle_message_command()?;
+        let user_id = self.interaction.author_id().ok()?;
+
+        self.ctx.range_starts.lock().unwrap().insert(
+            user_id,
+            RangeStart {
+                channel_id: message.channel_id,
+                message_id: message.id,
+                marked_at: Instant::now(),
+            },
+        );
+
+        self.handle
+            .reply(Reply::new().ephemeral().content(
+                "noted, now use *move range ending here* on the last message of the range",
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn handle_move_range_command(self) -> Result<()> {
+        let guild_id = self.interaction.guild_id.ok()?;
+        let message = self.handle_message_command()?;
+        let user_id = self.interaction.author_id().ok()?;
+
+        let start = self
+            .ctx
+            .range_starts
+            .lock()
+            .unwrap()
+            .remove(&user_id)
+            .filter(|start| start.marked_at.elapsed() <= RANGE_START_EXPIRY)
+            .ok_or(CustomError::NoRangeStart)?;
+        if start.channel_id != message.channel_id {
+            return Err(anyhow!(NotInSameChannel));
+        }
+
+        let channel = self.wait_for_channel_select_interaction().await?;
+        self.handle.reply(Reply::new().ephemeral().update_last().content("moving messages...")).await?;
+        let messages = self
+            .get_message_borned(message.channel_id, start.message_id, Some(message.id))
+            .await?;
+        self.handle.reply(Reply::new().ephemeral().update_last().content(format!("Moving {} messages", messages.len()))).await?;
+        let options = MoveOptions {
+            delete_old: DeleteOld::Messages,
+            ..MoveOptions::default()
+        };
+        let outcome = self.move_messages(&messages, &channel, guild_id, &options).await?;
+
+        self.bulk_delete(copied_only(&messages, &outcome), Some(guild_id))
+            .await?;
+        self.reply_move_report(guild_id, &messages, &outcome).await
+    }
+}
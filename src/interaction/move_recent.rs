@@ -0,0 +1,187 @@
+use crate::interaction::move_options::MoveCommandOptions;
+use crate::interaction::{dry_run, failure, forum, keep_reactions, large_attachments, InteractionContext};
+use crate::job::DeleteOld;
+use crate::REQUIRED_PERMISSIONS;
+use anyhow::{anyhow, Result};
+use sparkle_convenience::error::IntoError;
+use sparkle_convenience::interaction::extract::InteractionDataExt;
+use sparkle_convenience::reply::Reply;
+use std::collections::HashMap;
+use twilight_model::application::command::CommandOption;
+use twilight_model::application::command::{Command, CommandType};
+use twilight_model::application::interaction::application_command::CommandOptionValue;
+use twilight_model::channel::{Channel, Message};
+use twilight_model::id::marker::{ChannelMarker, UserMarker};
+use twilight_model::id::Id;
+use twilight_util::builder::command::{
+    BooleanBuilder, ChannelBuilder, CommandBuilder, IntegerBuilder, UserBuilder,
+};
+
+pub const CHAT_INPUT_NAME: &str = "move_recent";
+
+/// The most messages that can be moved at once with `count`
+const MAX_COUNT: i64 = 500;
+/// How far back the messages of `user` are looked for
+const MAX_SCANNED: usize = 2000;
+
+pub fn slash_command() -> Command {
+    let mut map = HashMap::new();
+    map.insert(
+        "fr".to_string(),
+        "Déplace les derniers messages du channel jusqu'au channel correspondant".to_string(),
+    );
+    CommandBuilder::new(
+        CHAT_INPUT_NAME,
+        "Move the most recent messages of this channel to the corresponding channel",
+        CommandType::ChatInput,
+    )
+    .default_member_permissions(REQUIRED_PERMISSIONS)
+    .dm_permission(false)
+    .option(CommandOption::from(
+        ChannelBuilder::new("channel", "the channel to move the messages to").required(true),
+    ))
+    .option(CommandOption::from(
+        IntegerBuilder::new("count", "how many messages to move")
+            .required(true)
+            .min_value(1)
+            .max_value(MAX_COUNT),
+    ))
+    .option(CommandOption::from(UserBuilder::new(
+        "user",
+        "only move the messages of this user",
+    )))
+    .option(CommandOption::from(BooleanBuilder::new(
+        "delete_old",
+        "delete the old messages",
+    )))
+    .option(dry_run::option())
+    .option(forum::post_title_option())
+    .option(forum::post_tags_option())
+    .option(failure::on_failure_option())
+    .option(failure::retries_option())
+    .option(keep_reactions::option())
+    .option(large_attachments::option())
+    .description_localizations(map.iter())
+    .validate()
+    .unwrap()
+    .build()
+}
+
+impl InteractionContext<'_> {
+    pub async fn handle_move_recent_cmd(self) -> Result<()> {
+        let mut result_channel: Option<Id<ChannelMarker>> = None;
+        let mut count: Option<i64> = None;
+        let mut user: Option<Id<UserMarker>> = None;
+        let mut remove = false;
+        let mut move_options = MoveCommandOptions::default();
+        if let Some(data) = self.interaction.data.clone() {
+            if let Some(command_data) = data.command() {
+                move_options = MoveCommandOptions::from_options(&command_data.options);
+                for option in &command_data.options {
+                    match option.name.as_str() {
+                        "channel" => {
+                            if let CommandOptionValue::Channel(id) = &option.value {
+                                result_channel = Some(*id);
+                            }
+                        }
+                        "count" => {
+                            if let CommandOptionValue::Integer(n) = &option.value {
+                                count = Some(*n);
+                            }
+                        }
+                        "user" => {
+                            if let CommandOptionValue::User(id) = &option.value {
+                                user = Some(*id);
+                            }
+                        }
+                        "delete_old" => {
+                            if let CommandOptionValue::Boolean(b) = &option.value {
+                                remove = *b;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let guild_id = self.interaction.guild_id.ok()?;
+        let input_channel = self.interaction.channel.as_ref().ok()?.id;
+        let result_channel = result_channel.ok_or_else(|| anyhow!("Missing parameters"))?;
+        let count = count
+            .and_then(|count| usize::try_from(count).ok())
+            .ok_or_else(|| anyhow!("Missing parameters"))?;
+        let r_channel: Channel = self
+            .ctx
+            .bot
+            .http
+            .channel(result_channel)
+            .await?
+            .model()
+            .await?;
+
+        self.handle.reply(Reply::new().ephemeral().update_last().content("Collecting messages...")).await?;
+        let messages = self.get_recent_messages(input_channel, count, user).await?;
+        let MoveCommandOptions { dry_run, mut options } = move_options;
+        if dry_run {
+            let mut actions = vec![format!("would be moved to: <#{}>", r_channel.id)];
+            if remove {
+                actions.push("the original messages would be deleted".to_owned());
+            }
+            return self.reply_dry_run(&messages, Some(guild_id), &actions).await;
+        }
+        self.handle.reply(Reply::new().ephemeral().update_last().content(format!("Moving {} messages", messages.len()))).await?;
+
+        if remove {
+            options.delete_old = DeleteOld::Messages;
+        }
+
+        Box::pin(self.move_messages_from_channel_to(guild_id, messages, r_channel, input_channel, options))
+            .await
+    }
+
+    /// The last `count` messages of `channel`, only the ones of `user` when
+    /// it's set, oldest first
+    async fn get_recent_messages(
+        &self,
+        channel: Id<ChannelMarker>,
+        count: usize,
+        user: Option<Id<UserMarker>>,
+    ) -> Result<Vec<Message>> {
+        let mut messages = Vec::new();
+        let mut last_message_id = None;
+        let mut scanned = 0;
+
+        while messages.len() < count && scanned < MAX_SCANNED {
+            let response = self
+                .ctx
+                .pacer
+                .run(|| async {
+                    let request = self.ctx.bot.http.channel_messages(channel).limit(100)?;
+                    Ok(if let Some(last_id) = last_message_id {
+                        request.before(last_id).await?
+                    } else {
+                        request.await?
+                    })
+                })
+                .await?;
+            self.ctx.pacer.observe(&response).await;
+            let channel_messages = response.model().await?;
+            let Some(last) = channel_messages.last() else {
+                break;
+            };
+            last_message_id = Some(last.id);
+            scanned += channel_messages.len();
+
+            messages.extend(
+                channel_messages
+                    .into_iter()
+                    .filter(|message| user.is_none_or(|user| message.author.id == user)),
+            );
+        }
+
+        messages.truncate(count);
+        messages.reverse();
+        Ok(messages)
+    }
+}
@@ -1,13 +1,19 @@
-use crate::interaction::InteractionContext;
-use crate::MessageInteractError::NotBoth;
+use crate::interaction::move_options::MoveCommandOptions;
+use crate::interaction::utils::copied_only;
+use crate::interaction::filter::{self, MessageFilter};
+use crate::interaction::other_server::{self, OtherServer};
+use crate::interaction::{dry_run, failure, forum, keep_reactions, large_attachments, InteractionContext};
+use crate::MessageInteractError::{ForumNotPost, NotBoth};
+use crate::job::{DeleteOld, MoveOptions};
 use crate::REQUIRED_PERMISSIONS;
 use anyhow::{anyhow, Result};
+use sparkle_convenience::error::IntoError;
 use sparkle_convenience::interaction::extract::InteractionDataExt;
 use sparkle_convenience::reply::Reply;
 use std::collections::HashMap;
 use twilight_model::application::command::CommandOption;
 use twilight_model::application::interaction::application_command::CommandOptionValue;
-use twilight_model::channel::{Channel, Message};
+use twilight_model::channel::{Channel, ChannelType, Message};
 use twilight_model::id::marker::{ChannelMarker, GuildMarker};
 use twilight_model::id::Id;
 use twilight_model::application::command::{Command, CommandType};
@@ -23,7 +29,7 @@ pub fn slash_command() -> Command {
         "fr".to_string(),
         "Déplace un channel jusqu'au channel correspondant".to_string(),
     );
-    CommandBuilder::new(
+    let command = CommandBuilder::new(
         CHAT_INPUT_NAME,
         "Move messages between channels (uses current channel if none specified)",
         CommandType::ChatInput,
@@ -39,11 +45,20 @@ pub fn slash_command() -> Command {
         .option(CommandOption::from(
             StringBuilder::new("channel_to_name", "the channel where to move the channel to").required(false),
         ))
+        .option(other_server::server_option())
+        .option(other_server::channel_option())
         .option(CommandOption::from(BooleanBuilder::new(
             "delete_old",
             "delete the old channel",
         )))
-        //.default_member_permissions(REQUIRED_PERMISSIONS)
+        .option(dry_run::option())
+        .option(forum::post_title_option())
+        .option(forum::post_tags_option())
+        .option(failure::on_failure_option())
+        .option(failure::retries_option())
+        .option(keep_reactions::option())
+        .option(large_attachments::option());
+    filter::add_options(command)
         .description_localizations(map.iter())
         .build()
 }
@@ -57,8 +72,14 @@ impl InteractionContext<'_> {
         let mut remove: Option<bool> = None;
         let mut input_channel: Option<Channel> = None;
         let mut result_channel_name: Option<String> = None;
+        let mut move_options = MoveCommandOptions::default();
+        let mut filter = MessageFilter::default();
+        let mut other_server = OtherServer::default();
         if let Some(data) = self.interaction.data.clone() {
             if let Some(command_data) = data.command() {
+                move_options = MoveCommandOptions::from_options(&command_data.options);
+                filter = MessageFilter::from_options(&command_data.options)?;
+                other_server = OtherServer::from_options(&command_data.options)?;
                 for option in &command_data.options {
                     match option.name.as_str() {
                         "channel_from" => {
@@ -86,14 +107,40 @@ impl InteractionContext<'_> {
                 }
             }
         }
+        if let Some(channel_id) = other_server.channel_id {
+            if result_channel.is_some() {
+                return Err(anyhow!(NotBoth("channel_to".to_string(), other_server::CHANNEL_OPTION_NAME.to_string())));
+            }
+            result_channel = Some(self.ctx.bot.http.channel(channel_id).await?.model().await?);
+        }
         match (&result_channel, &result_channel_name) {
             (Some(_), Some(_)) => return Err(anyhow!(NotBoth("channel_to".to_string(), "channel_to_name".to_string()))),
             (None, None) => return Err(anyhow!(NotBoth("channel_to".to_string(), "channel_to_name".to_string()))),
             _=>{}
         }
+        if input_channel.as_ref().is_some_and(|channel| channel.kind == ChannelType::GuildForum) {
+            return Err(anyhow!(ForumNotPost));
+        }
+        let MoveCommandOptions { dry_run, mut options } = move_options;
+        if dry_run {
+            let input_channel = input_channel.or_else(|| self.interaction.channel.clone()).ok()?;
+            self.handle.reply(Reply::new().ephemeral().update_last().content("Collecting messages...")).await?;
+            let messages = self.get_all_messages_from_beginning(input_channel.id).await?;
+            let messages = self.filter_messages(messages, &filter, self.interaction.guild_id.ok()?).await?;
+            let mut actions = vec![match (&result_channel, &result_channel_name) {
+                (Some(channel), _) => format!("would be moved to: <#{}>", channel.id),
+                (None, name) => format!("would be moved to a new channel named `{}`", name.clone().unwrap_or_default()),
+            }];
+            if remove.unwrap_or(false) && filter.is_empty() {
+                actions.push(format!("<#{}> would be deleted", input_channel.id));
+            } else if remove.unwrap_or(false) {
+                actions.push("the moved messages would be deleted".to_owned());
+            }
+            return self.reply_dry_run(&messages, self.interaction.guild_id, &actions).await;
+        }
         if(result_channel.is_none()) {
-            let channel = self.ctx.bot.http.create_guild_channel(self.interaction.guild_id.unwrap(), result_channel_name.unwrap().as_str())?.await;
-            result_channel =  Some(channel?.model().await?)
+            let channel = self.create_target_channel(other_server.guild_id, result_channel_name.unwrap().as_str()).await;
+            result_channel =  Some(channel?)
         }
         let messages: Vec<Message>;
         let int = self.interaction.clone();
@@ -104,25 +151,43 @@ impl InteractionContext<'_> {
         
         self.handle.reply(Reply::new().ephemeral().update_last().content("Moving messages...")).await?;
         messages = self.get_all_messages_from_beginning(input_channel.as_ref().unwrap().id).await?;
+        let messages = self.filter_messages(messages, &filter, int.guild_id.ok()?).await?;
         self.handle.reply(Reply::new().ephemeral().update_last().content(format!("Found {} messages", messages.len()))).await?;
         let guild_id = int.guild_id.unwrap();
-        
-        self.move_messages_from_channel_to(guild_id, messages, result_channel.unwrap(), remove.unwrap_or(false), input_channel.unwrap().id, false)
+        let input_channel = input_channel.unwrap();
+        // The messages left out by the filter would go with the channel
+        if remove.unwrap_or(false) && filter.is_empty() {
+            options.delete_old = DeleteOld::Channel;
+        } else if remove.unwrap_or(false) {
+            options.delete_old = DeleteOld::Messages;
+        }
+        // A post moved to a forum keeps its title
+        if input_channel.kind.is_thread() && options.post_title.is_none() {
+            options.post_title = input_channel.name.clone();
+        }
+
+        Box::pin(self.move_messages_from_channel_to(guild_id, messages, result_channel.unwrap(), input_channel.id, options))
             .await?;
         Ok(())
     }
 
-    pub async fn move_messages_from_channel_to(self, guild_id: Id<GuildMarker>, messages: Vec<Message>, r_channel: Channel, remove_channel: bool, i_channel: Id<ChannelMarker>, remove_msg:bool) -> Result<()> {
+    pub async fn move_messages_from_channel_to(self, guild_id: Id<GuildMarker>, messages: Vec<Message>, r_channel: Channel, i_channel: Id<ChannelMarker>, options: MoveOptions) -> Result<()> {
 
-        self.move_messages(&messages, &r_channel, guild_id, None).await?;
-        if !remove_channel && remove_msg {
-            self.bulk_delete(messages, Some(guild_id)).await?;
-        }
-        if remove_channel {
-            self.ctx.bot.http.delete_channel(i_channel).await?;
+        let outcome = self.move_messages(&messages, &r_channel, guild_id, &options).await?;
+        match options.delete_old {
+            DeleteOld::Nothing => {}
+            DeleteOld::Messages => {
+                self.bulk_delete(copied_only(&messages, &outcome), Some(guild_id))
+                    .await?;
+            }
+            // The messages that weren't copied would go with the channel
+            DeleteOld::Channel if outcome.copied.len() < messages.len() => {}
+            DeleteOld::Channel => {
+                self.ctx.bot.http.delete_channel(i_channel).await?;
+                self.record_source_channel_deleted();
+            }
         }
-        self.handle.reply(Reply::new().ephemeral().update_last().content("Done!")).await?;
 
-        Ok(())
+        self.reply_move_report(guild_id, &messages, &outcome).await
     }
 }
\ No newline at end of file
@@ -0,0 +1,237 @@
+use crate::interaction::move_options::MoveCommandOptions;
+use crate::interaction::move_message_and_below::parse_message_link;
+use crate::interaction::{dry_run, failure, keep_reactions, large_attachments, InteractionContext};
+use crate::job::{DeleteOld, MoveOptions};
+use crate::message::{default_thread_name, truncate_thread_name};
+use crate::MessageInteractError::{IdNotFoundLink, NotInSameChannel, ThreadInThread};
+use crate::REQUIRED_PERMISSIONS;
+use anyhow::{anyhow, Result};
+use sparkle_convenience::error::IntoError;
+use sparkle_convenience::interaction::extract::InteractionDataExt;
+use sparkle_convenience::reply::Reply;
+use std::collections::HashMap;
+use twilight_model::application::command::CommandOption;
+use twilight_model::application::command::{Command, CommandType};
+use twilight_model::application::interaction::application_command::CommandOptionValue;
+use twilight_model::channel::{Channel, ChannelType, Message};
+use twilight_model::id::marker::{ChannelMarker, GuildMarker, MessageMarker};
+use twilight_model::id::Id;
+use twilight_util::builder::command::{
+    BooleanBuilder, ChannelBuilder, CommandBuilder, StringBuilder,
+};
+
+pub const NAME: &str = "move to new thread";
+pub const CHAT_INPUT_NAME: &str = "move_to_new_thread";
+
+pub fn command() -> Command {
+    CommandBuilder::new(NAME, "", CommandType::Message)
+        .dm_permission(false)
+        .default_member_permissions(REQUIRED_PERMISSIONS)
+        .build()
+}
+
+pub fn slash_command() -> Command {
+    let mut map = HashMap::new();
+    map.insert(
+        "fr".to_string(),
+        "Déplace une partie des msgs d'un channel dans un nouveau fil".to_string(),
+    );
+    CommandBuilder::new(
+        CHAT_INPUT_NAME,
+        "Move messages to a new thread",
+        CommandType::ChatInput,
+    )
+        .default_member_permissions(REQUIRED_PERMISSIONS)
+        .dm_permission(false)
+        .option(CommandOption::from(
+            StringBuilder::new("message_from", "Source message url").required(true),
+        ))
+        .option(CommandOption::from(
+            StringBuilder::new("message_to", "Source message url end").required(true),
+        ))
+        .option(CommandOption::from(
+            StringBuilder::new("thread_name", "the name of the thread")
+                .required(true)
+                .max_length(100),
+        ))
+        .option(CommandOption::from(
+            ChannelBuilder::new("channel", "where to create the thread (default: the messages' channel)")
+                .channel_types([ChannelType::GuildText, ChannelType::GuildAnnouncement]),
+        ))
+        .option(CommandOption::from(BooleanBuilder::new(
+            "private",
+            "create a private thread",
+        )))
+        .option(CommandOption::from(BooleanBuilder::new(
+            "delete_old",
+            "delete the old messages",
+        )))
+        .option(dry_run::option())
+        .option(failure::on_failure_option())
+        .option(failure::retries_option())
+        .option(keep_reactions::option())
+        .option(large_attachments::option())
+        .description_localizations(map.iter())
+        .build()
+}
+
+impl InteractionContext<'_> {
+    pub async fn handle_move_to_new_thread_command(self) -> Result<()> {
+        let guild_id = self.interaction.guild_id.ok()?;
+        let message = self.handle_message_command()?;
+
+        self.handle.reply(Reply::new().ephemeral().update_last().content("Creating the thread...")).await?;
+        let channel = self.ctx.bot.http.channel(message.channel_id).await?.model().await?;
+        let messages = self.get_message_borned(message.channel_id, message.id, None).await?;
+        let thread = self
+            .create_thread_for(&channel, &default_thread_name(&message), false)
+            .await?;
+
+        let options = MoveOptions {
+            delete_old: DeleteOld::Messages,
+            ..MoveOptions::default()
+        };
+        Box::pin(self.move_messages_to_new_thread(guild_id, messages, thread, options)).await
+    }
+
+    pub async fn handle_move_to_new_thread_cmd(self) -> Result<()> {
+        let mut from_message: Option<Id<MessageMarker>> = None;
+        let mut to_message: Option<Id<MessageMarker>> = None;
+        let mut input_channel: Option<Id<ChannelMarker>> = None;
+        let mut thread_channel: Option<Id<ChannelMarker>> = None;
+        let mut thread_name: Option<String> = None;
+        let mut private = false;
+        let mut remove = false;
+        let mut move_options = MoveCommandOptions::default();
+        if let Some(data) = self.interaction.data.clone() {
+            if let Some(command_data) = data.command() {
+                move_options = MoveCommandOptions::from_options(&command_data.options);
+                for option in &command_data.options {
+                    match option.name.as_str() {
+                        "message_from" | "message_to" => {
+                            if let CommandOptionValue::String(link) = &option.value {
+                                let (_, channel_id, message_id) = parse_message_link(link)?;
+                                if input_channel.is_some_and(|id| id != channel_id) {
+                                    return Err(anyhow!(NotInSameChannel));
+                                }
+                                input_channel = Some(channel_id);
+                                if option.name == "message_from" {
+                                    from_message = Some(message_id);
+                                } else {
+                                    to_message = Some(message_id);
+                                }
+                            }
+                        }
+                        "thread_name" => {
+                            if let CommandOptionValue::String(name) = &option.value {
+                                thread_name = Some(name.clone());
+                            }
+                        }
+                        "channel" => {
+                            if let CommandOptionValue::Channel(id) = &option.value {
+                                thread_channel = Some(*id);
+                            }
+                        }
+                        "private" => {
+                            if let CommandOptionValue::Boolean(b) = &option.value {
+                                private = *b;
+                            }
+                        }
+                        "delete_old" => {
+                            if let CommandOptionValue::Boolean(b) = &option.value {
+                                remove = *b;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let (Some(from_message), Some(input_channel)) = (from_message, input_channel) else {
+            return Err(anyhow!(IdNotFoundLink));
+        };
+        let thread_name = thread_name.ok_or_else(|| anyhow!("Missing parameters"))?;
+        let guild_id = self.interaction.guild_id.ok()?;
+
+        self.handle.reply(Reply::new().ephemeral().update_last().content("Collecting messages...")).await?;
+        let messages = self.get_message_borned(input_channel, from_message, to_message).await?;
+        let parent = self
+            .ctx
+            .bot
+            .http
+            .channel(thread_channel.unwrap_or(input_channel))
+            .await?
+            .model()
+            .await?;
+        let MoveCommandOptions { dry_run, mut options } = move_options;
+        if dry_run {
+            let mut actions = vec![format!(
+                "would be moved to a new {} thread `{thread_name}` in <#{}>",
+                if private { "private" } else { "public" },
+                parent.id
+            )];
+            if remove {
+                actions.push("the original messages would be deleted".to_owned());
+            }
+            return self.reply_dry_run(&messages, Some(guild_id), &actions).await;
+        }
+
+        let thread = self.create_thread_for(&parent, &thread_name, private).await?;
+        if remove {
+            options.delete_old = DeleteOld::Messages;
+        }
+        Box::pin(self.move_messages_to_new_thread(guild_id, messages, thread, options)).await
+    }
+
+    async fn create_thread_for(&self, parent: &Channel, name: &str, private: bool) -> Result<Channel> {
+        if parent.kind.is_thread() {
+            return Err(anyhow!(ThreadInThread));
+        }
+
+        let kind = if private {
+            ChannelType::PrivateThread
+        } else if parent.kind == ChannelType::GuildAnnouncement {
+            ChannelType::AnnouncementThread
+        } else {
+            ChannelType::PublicThread
+        };
+        let thread = self
+            .ctx
+            .bot
+            .http
+            .create_thread(parent.id, &truncate_thread_name(name), kind)?
+            .await?
+            .model()
+            .await?;
+
+        // Nobody but the bot is in a new private thread
+        if private {
+            if let Some(user_id) = self.interaction.author_id() {
+                self.ctx.bot.http.add_thread_member(thread.id, user_id).await?;
+            }
+        }
+
+        Ok(thread)
+    }
+
+    async fn move_messages_to_new_thread(
+        self,
+        guild_id: Id<GuildMarker>,
+        messages: Vec<Message>,
+        thread: Channel,
+        options: MoveOptions,
+    ) -> Result<()> {
+        let source_channel = messages.first().ok()?.channel_id;
+        self.start_move_record();
+        self.record_created_channel(thread.id);
+        self.handle.reply(Reply::new().ephemeral().update_last().content(format!("Moving {} messages to <#{}>", messages.len(), thread.id))).await?;
+
+        // Hiding a thread would hide its parent, where the conversation goes on
+        let options = MoveOptions {
+            hide_channel: false,
+            ..options
+        };
+        Box::pin(self.move_messages_from_channel_to(guild_id, messages, thread, source_channel, options)).await
+    }
+}
@@ -1,14 +1,19 @@
+use crate::interaction::move_options::MoveCommandOptions;
 use crate::interaction::move_message_and_below::parse_message_link;
-use crate::interaction::InteractionContext;
+use crate::interaction::filter::{self, MessageFilter};
+use crate::interaction::other_server::{self, OtherServer};
+use crate::interaction::{dry_run, failure, forum, keep_reactions, large_attachments, InteractionContext};
+use crate::job::DeleteOld;
 use crate::MessageInteractError::{IdNotFoundLink, NotBoth, NotInSameChannel};
 use crate::REQUIRED_PERMISSIONS;
 use anyhow::{anyhow, Result};
+use sparkle_convenience::error::IntoError;
 use sparkle_convenience::interaction::extract::InteractionDataExt;
 use sparkle_convenience::reply::Reply;
 use std::collections::HashMap;
 use twilight_model::application::command::CommandOption;
 use twilight_model::application::interaction::application_command::CommandOptionValue;
-use twilight_model::channel::{Channel, Message};
+use twilight_model::channel::Channel;
 use twilight_model::id::marker::{ChannelMarker, MessageMarker};
 use twilight_model::id::Id;
 use twilight_model::application::command::{Command, CommandType};
@@ -24,7 +29,7 @@ pub fn slashCommand() -> Command {
         "fr".to_string(),
         "Déplace une partie des msgs d'un channel jusqu'à un autre".to_string(),
     );
-    CommandBuilder::new(
+    let command = CommandBuilder::new(
         CHAT_INPUT_NAME,
         "Move messages between channels (uses current channel if none specified)",
         CommandType::ChatInput,
@@ -32,10 +37,10 @@ pub fn slashCommand() -> Command {
         .default_member_permissions(REQUIRED_PERMISSIONS)
         .dm_permission(false)
         .option(CommandOption::from(
-            StringBuilder::new("message_from", "Source message url").required(true),
+            StringBuilder::new("message_from", "Source message url, or use after and before").required(false),
         ))
         .option(CommandOption::from(
-            StringBuilder::new("message_to", "Source message url end").required(true),
+            StringBuilder::new("message_to", "Source message url end").required(false),
         ))
         .option(CommandOption::from(
             ChannelBuilder::new("channel_to", "Target channel").required(false),
@@ -43,11 +48,20 @@ pub fn slashCommand() -> Command {
         .option(CommandOption::from(
             StringBuilder::new("channel_to_name", "Target channel").required(false),
         ))
+        .option(other_server::server_option())
+        .option(other_server::channel_option())
         .option(CommandOption::from(BooleanBuilder::new(
             "delete_old",
             "delete the old channel",
         )))
-        //.default_member_permissions(REQUIRED_PERMISSIONS)
+        .option(dry_run::option())
+        .option(forum::post_title_option())
+        .option(forum::post_tags_option())
+        .option(failure::on_failure_option())
+        .option(failure::retries_option())
+        .option(keep_reactions::option())
+        .option(large_attachments::option());
+    filter::add_options(command)
         .description_localizations(map.iter())
         .build()
 }
@@ -61,35 +75,27 @@ impl InteractionContext<'_> {
         let mut input_channel: Option<Id<ChannelMarker>> = None;
         let mut to_message: Option<Id<MessageMarker>> = None;
         let mut result_channel_name: Option<String> = None;
+        let mut move_options = MoveCommandOptions::default();
+        let mut filter = MessageFilter::default();
+        let mut other_server = OtherServer::default();
         if let Some(data) = self.interaction.data.clone() {
             if let Some(command_data) = data.command() {
+                move_options = MoveCommandOptions::from_options(&command_data.options);
+                filter = MessageFilter::from_options(&command_data.options)?;
+                other_server = OtherServer::from_options(&command_data.options)?;
                 for option in &command_data.options {
                     match option.name.as_str() {
-                        "message_from" => {
-                            if let CommandOptionValue::String(id) = &option.value {
-                                let parsed = parse_message_link(&id);
-                                let p = parsed?;
-                                from_message = Some(p.2);
-                                if input_channel.is_none() {
-                                    input_channel = Some(p.1);
-                                }else{
-                                    if(input_channel.unwrap() != p.1){
-                                        return Err(anyhow!(NotInSameChannel))
-                                    }
+                        "message_from" | "message_to" => {
+                            if let CommandOptionValue::String(link) = &option.value {
+                                let (_, channel_id, message_id) = parse_message_link(link)?;
+                                if input_channel.is_some_and(|id| id != channel_id) {
+                                    return Err(anyhow!(NotInSameChannel));
                                 }
-                            }
-                        }
-                        "message_to" => {
-                            if let CommandOptionValue::String(id) = &option.value {
-                                let parsed = parse_message_link(&id);
-                                let p = parsed?;
-                                to_message = Some(p.2);
-                                if input_channel.is_none() {
-                                    input_channel = Some(p.1);
-                                }else{
-                                    if input_channel.unwrap() != p.1 {
-                                        return Err(anyhow!(NotInSameChannel))
-                                    }
+                                input_channel = Some(channel_id);
+                                if option.name == "message_from" {
+                                    from_message = Some(message_id);
+                                } else {
+                                    to_message = Some(message_id);
                                 }
                             }
                         }
@@ -114,29 +120,56 @@ impl InteractionContext<'_> {
             }
         }
 
+        if let Some(channel_id) = other_server.channel_id {
+            if result_channel.is_some() {
+                return Err(anyhow!(NotBoth("channel_to".to_string(), other_server::CHANNEL_OPTION_NAME.to_string())));
+            }
+            result_channel = Some(self.ctx.bot.http.channel(channel_id).await?.model().await?);
+        }
         match (&result_channel, &result_channel_name) {
-            (Some(_), Some(_)) => return Err(anyhow!(NotBoth("channel_to".to_string(), "channel_to_name".to_string()))),
-            (None, None) => return Err(anyhow!(NotBoth("channel_to".to_string(), "channel_to_name".to_string()))),
+            (Some(_), Some(_)) | (None, None) => {
+                return Err(anyhow!(NotBoth("channel_to".to_string(), "channel_to_name".to_string())))
+            }
             _ => {}
         }
-        if(result_channel.is_none()) {
-            let channel = self.ctx.bot.http.create_guild_channel(self.interaction.guild_id.unwrap(), result_channel_name.unwrap().as_str())?.await;
-            result_channel =  Some(channel?.model().await?)
-        }
-        if(from_message.is_none() || to_message.is_none()){
+        if from_message.is_some() != to_message.is_some() {
             return Err(anyhow!(IdNotFoundLink))
         }
-
-        let messages: Vec<Message>;
-        let int = self.interaction.clone();
-
+        // Without links, the messages are taken from the current channel
+        let input_channel = match input_channel {
+            Some(channel) => channel,
+            None => self.interaction.channel.as_ref().ok()?.id,
+        };
+        let MoveCommandOptions { dry_run, mut options } = move_options;
+        if dry_run {
+            self.handle.reply(Reply::new().ephemeral().update_last().content("Collecting messages...")).await?;
+            let messages = self.get_message_range(input_channel, from_message, to_message, filter.window()).await?;
+            let messages = self.filter_messages(messages, &filter, self.interaction.guild_id.ok()?).await?;
+            let mut actions = vec![match (&result_channel, &result_channel_name) {
+                (Some(channel), _) => format!("would be moved to: <#{}>", channel.id),
+                (None, name) => format!("would be moved to a new channel named `{}`", name.clone().unwrap_or_default()),
+            }];
+            if remove.unwrap_or(false) {
+                actions.push("the original messages would be deleted".to_owned());
+            }
+            return self.reply_dry_run(&messages, self.interaction.guild_id, &actions).await;
+        }
+        if(result_channel.is_none()) {
+            let channel = self.create_target_channel(other_server.guild_id, result_channel_name.unwrap().as_str()).await;
+            result_channel =  Some(channel?)
+        }
 
         self.handle.reply(Reply::new().ephemeral().update_last().content("Moving messages...")).await?;
-        messages = self.get_message_borned(input_channel.unwrap(), from_message.unwrap(), to_message).await?;
+        let messages = self.get_message_range(input_channel, from_message, to_message, filter.window()).await?;
+        let messages = self.filter_messages(messages, &filter, self.interaction.guild_id.ok()?).await?;
         self.handle.reply(Reply::new().ephemeral().update_last().content(format!("Found {} messages", messages.len()))).await?;
-        let guild_id = int.guild_id.unwrap();
+        let guild_id = self.interaction.guild_id.ok()?;
         
-        self.move_messages_from_channel_to(guild_id, messages, result_channel.unwrap(), false, input_channel.unwrap(), remove.unwrap_or(false))
+        if remove.unwrap_or(false) {
+            options.delete_old = DeleteOld::Messages;
+        }
+
+        Box::pin(self.move_messages_from_channel_to(guild_id, messages, result_channel.unwrap(), input_channel, options))
             .await?;
         Ok(())
     }
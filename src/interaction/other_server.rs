@@ -0,0 +1,209 @@
+use anyhow::{anyhow, Result};
+use futures::future::join_all;
+use sparkle_convenience::error::IntoError;
+use sparkle_convenience::interaction::extract::InteractionDataExt;
+use std::str::FromStr;
+use twilight_model::application::command::{CommandOption, CommandOptionChoice, CommandOptionChoiceValue};
+use twilight_model::application::interaction::application_command::{CommandDataOption, CommandOptionValue};
+use twilight_model::channel::{Channel, ChannelType};
+use twilight_model::guild::Permissions;
+use twilight_model::id::marker::{ChannelMarker, GuildMarker, UserMarker};
+use twilight_model::id::Id;
+use twilight_util::builder::command::StringBuilder;
+
+use crate::interaction::InteractionContext;
+use crate::permissions::check_target;
+use crate::MessageInteractError::NotPicked;
+use crate::{Context, CustomError};
+
+pub const SERVER_OPTION_NAME: &str = "server_to";
+pub const CHANNEL_OPTION_NAME: &str = "server_channel_to";
+
+/// The most choices Discord shows in a list
+const MAX_CHOICES: usize = 25;
+/// The longest name a choice can have
+const CHOICE_NAME_MAX_LENGTH: usize = 100;
+
+pub fn server_option() -> CommandOption {
+    CommandOption::from(
+        StringBuilder::new(SERVER_OPTION_NAME, "another server to move the messages to, pick it from the list")
+            .autocomplete(true),
+    )
+}
+
+pub fn channel_option() -> CommandOption {
+    CommandOption::from(
+        StringBuilder::new(CHANNEL_OPTION_NAME, "the channel of server_to to move the messages to")
+            .autocomplete(true),
+    )
+}
+
+/// The server picked with `server_to` and its channel picked with
+/// `server_channel_to`
+#[derive(Debug, Default)]
+pub struct OtherServer {
+    pub guild_id: Option<Id<GuildMarker>>,
+    pub channel_id: Option<Id<ChannelMarker>>,
+}
+
+impl OtherServer {
+    pub fn from_options(options: &[CommandDataOption]) -> Result<Self> {
+        let mut other_server = Self::default();
+        for option in options {
+            match (option.name.as_str(), &option.value) {
+                (SERVER_OPTION_NAME, CommandOptionValue::String(id)) => {
+                    other_server.guild_id = Some(parse_picked(id, "server")?);
+                }
+                (CHANNEL_OPTION_NAME, CommandOptionValue::String(id)) => {
+                    other_server.channel_id = Some(parse_picked(id, "channel")?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(other_server)
+    }
+}
+
+/// The ID a choice was picked with, anything else was typed without picking
+fn parse_picked<T>(id: &str, what: &str) -> Result<Id<T>> {
+    Id::from_str(id).map_err(|_| anyhow!(NotPicked(what.to_owned())))
+}
+
+impl InteractionContext<'_> {
+    /// Lists the servers the user is in, or the channels of the picked
+    /// server both the user and the bot can send messages in
+    pub async fn handle_other_server_autocomplete(self) -> Result<()> {
+        let data = self.interaction.data.clone().ok()?;
+        let command_data = data.command().ok()?;
+        let user_id = self.interaction.author_id().ok()?;
+
+        let mut focused = None;
+        let mut guild_id = None;
+        for option in &command_data.options {
+            match &option.value {
+                CommandOptionValue::Focused(query, _) => {
+                    focused = Some((option.name.as_str(), query.to_lowercase()));
+                }
+                CommandOptionValue::String(id) if option.name == SERVER_OPTION_NAME => {
+                    guild_id = Id::from_str(id).ok();
+                }
+                _ => {}
+            }
+        }
+
+        let choices = match (focused, guild_id) {
+            (Some((SERVER_OPTION_NAME, query)), _) => {
+                self.ctx
+                    .server_choices(user_id, self.interaction.guild_id, &query)
+                    .await?
+            }
+            (Some((CHANNEL_OPTION_NAME, query)), Some(guild_id)) => {
+                self.ctx.channel_choices(guild_id, user_id, &query).await?
+            }
+            _ => Vec::new(),
+        };
+        self.handle.autocomplete(choices).await?;
+
+        Ok(())
+    }
+
+    /// A new channel named `name` in `guild_id`, or in the guild the command
+    /// was used in if it's not set
+    pub async fn create_target_channel(&self, guild_id: Option<Id<GuildMarker>>, name: &str) -> Result<Channel> {
+        let current_guild_id = self.interaction.guild_id.ok()?;
+        let guild_id = guild_id.unwrap_or(current_guild_id);
+        if guild_id != current_guild_id {
+            let guild = self.ctx.bot.http.guild(guild_id).await?.model().await?;
+            let permissions = self
+                .ctx
+                .member_permissions(&guild, self.interaction.author_id().ok()?)
+                .await?;
+            if !permissions.root().contains(Permissions::MANAGE_CHANNELS) {
+                return Err(CustomError::ManageChannelsPermissionMissing.into());
+            }
+        }
+
+        Ok(self
+            .ctx
+            .bot
+            .http
+            .create_guild_channel(guild_id, name)?
+            .await?
+            .model()
+            .await?)
+    }
+}
+
+impl Context {
+    /// The servers other than `current_guild_id` that `user_id` is in, with
+    /// `query` in their name
+    async fn server_choices(
+        &self,
+        user_id: Id<UserMarker>,
+        current_guild_id: Option<Id<GuildMarker>>,
+        query: &str,
+    ) -> Result<Vec<CommandOptionChoice>> {
+        let guilds = self.bot.http.current_user_guilds().await?.models().await?;
+        let memberships = guilds
+            .into_iter()
+            .filter(|guild| Some(guild.id) != current_guild_id)
+            .filter(|guild| guild.name.to_lowercase().contains(query))
+            .map(|guild| async move {
+                let is_member = self.bot.http.guild_member(guild.id, user_id).await.is_ok();
+                is_member.then_some(guild)
+            });
+
+        Ok(join_all(memberships)
+            .await
+            .into_iter()
+            .flatten()
+            .take(MAX_CHOICES)
+            .map(|guild| choice(&guild.name, guild.id.to_string()))
+            .collect())
+    }
+
+    /// The channels of `guild_id` both `user_id` and the bot can send
+    /// messages in, with `query` in their name
+    async fn channel_choices(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        query: &str,
+    ) -> Result<Vec<CommandOptionChoice>> {
+        let guild = self.bot.http.guild(guild_id).await?.model().await?;
+        let Ok(user) = self.member_permissions(&guild, user_id).await else {
+            return Ok(Vec::new());
+        };
+        let bot = self.member_permissions(&guild, self.bot.user.id).await?;
+
+        let mut channels = self.bot.http.guild_channels(guild_id).await?.models().await?;
+        channels.sort_by_key(|channel| channel.position);
+
+        Ok(channels
+            .iter()
+            .filter(|channel| {
+                matches!(
+                    channel.kind,
+                    ChannelType::GuildText | ChannelType::GuildAnnouncement | ChannelType::GuildForum
+                )
+            })
+            .filter_map(|channel| Some((channel, channel.name.as_deref()?)))
+            .filter(|(_, name)| name.to_lowercase().contains(query))
+            .filter(|(channel, _)| {
+                let overwrites = channel.permission_overwrites.clone().unwrap_or_default();
+                check_target(&user, &bot, channel, &overwrites).is_ok()
+            })
+            .take(MAX_CHOICES)
+            .map(|(channel, name)| choice(&format!("#{name}"), channel.id.to_string()))
+            .collect())
+    }
+}
+
+fn choice(name: &str, value: String) -> CommandOptionChoice {
+    CommandOptionChoice {
+        name: name.chars().take(CHOICE_NAME_MAX_LENGTH).collect(),
+        name_localizations: None,
+        value: CommandOptionChoiceValue::String(value),
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use anyhow::Result;
+use sparkle_convenience::error::IntoError;
+use sparkle_convenience::reply::Reply;
+use tokio::time::Instant;
+use twilight_model::application::command::{Command, CommandType};
+use twilight_model::channel::Message;
+use twilight_model::id::marker::{ChannelMarker, InteractionMarker, MessageMarker, UserMarker};
+use twilight_model::id::Id;
+use twilight_util::builder::command::CommandBuilder;
+
+use crate::interaction::InteractionContext;
+use crate::message::MovedCopies;
+use crate::{Context, CustomError, REQUIRED_PERMISSIONS};
+
+pub const CHAT_INPUT_NAME: &str = "undo_move";
+
+/// How long after a move it can still be undone.
+pub const UNDO_WINDOW: Duration = Duration::from_hours(1);
+
+pub fn slash_command() -> Command {
+    let mut map = HashMap::new();
+    map.insert(
+        "fr".to_string(),
+        "Annule ton dernier déplacement de messages".to_string(),
+    );
+    CommandBuilder::new(
+        CHAT_INPUT_NAME,
+        "Undo your last move (within an hour)",
+        CommandType::ChatInput,
+    )
+    .default_member_permissions(REQUIRED_PERMISSIONS)
+    .dm_permission(false)
+    .description_localizations(map.iter())
+    .build()
+}
+
+/// What a move did, so that it can be undone.
+pub struct MoveRecord {
+    interaction_id: Id<InteractionMarker>,
+    /// The original messages with their copy, in the order they were sent
+    copies: Vec<(Message, Message)>,
+    deleted: HashSet<Id<MessageMarker>>,
+    source_channel_deleted: bool,
+    /// The threads and forum posts the move created, deleted with the copies
+    created_channels: Vec<Id<ChannelMarker>>,
+    /// Set once the interaction that started the move is handled, the move
+    /// can't be undone while it's still copying or deleting messages and the
+    /// undo window starts then
+    finished_at: Option<Instant>,
+    /// Set while the move is being undone, so that it isn't undone twice
+    undoing: bool,
+}
+
+impl InteractionContext<'_> {
+    /// Forgets the previous move of the user, starting a new record for this
+    /// interaction unless it already has one.
+    pub fn start_move_record(&self) {
+        let Some(user_id) = self.interaction.author_id() else {
+            return;
+        };
+        let mut records = self.ctx.move_records.lock().unwrap();
+        if records
+            .get(&user_id)
+            .is_some_and(|record| record.interaction_id == self.interaction.id)
+        {
+            return;
+        }
+        records.insert(
+            user_id,
+            MoveRecord {
+                interaction_id: self.interaction.id,
+                copies: Vec::new(),
+                deleted: HashSet::new(),
+                source_channel_deleted: false,
+                created_channels: Vec::new(),
+                finished_at: None,
+                undoing: false,
+            },
+        );
+    }
+
+    /// Records the copies of `original`, more than one when its attachments
+    /// were split
+    pub fn record_copy(&self, original: &Message, copies: &[Message]) {
+        self.with_move_record(|record| {
+            // The first message of a forum post has the ID of the post
+            record.created_channels.extend(
+                copies
+                    .iter()
+                    .filter(|copy| copy.id.cast() == copy.channel_id)
+                    .map(|copy| copy.channel_id),
+            );
+            record
+                .copies
+                .extend(copies.iter().map(|copy| (original.clone(), copy.clone())));
+        });
+    }
+
+    pub fn record_created_channel(&self, channel_id: Id<ChannelMarker>) {
+        self.with_move_record(|record| record.created_channels.push(channel_id));
+    }
+
+    pub fn record_deleted(&self, message_ids: &[Id<MessageMarker>]) {
+        self.with_move_record(|record| record.deleted.extend(message_ids));
+    }
+
+    pub fn record_source_channel_deleted(&self) {
+        self.with_move_record(|record| record.source_channel_deleted = true);
+    }
+
+    fn with_move_record(&self, f: impl FnOnce(&mut MoveRecord)) {
+        let Some(user_id) = self.interaction.author_id() else {
+            return;
+        };
+        self.ctx.with_move_record(user_id, self.interaction.id, f);
+    }
+
+    pub async fn handle_undo_move_cmd(self) -> Result<()> {
+        let user_id = self.interaction.author_id().ok()?;
+        // The record stays until the undo is done, so that a failed undo can
+        // be tried again and only what's left is undone then
+        let (interaction_id, copies_count, to_repost) = {
+            let mut records = self.ctx.move_records.lock().unwrap();
+            let record = records.get_mut(&user_id).ok_or(CustomError::NothingToUndo)?;
+            let finished_at = record.finished_at.ok_or(CustomError::MoveInProgress)?;
+            if record.undoing {
+                return Err(CustomError::MoveInProgress.into());
+            }
+            if finished_at.elapsed() > UNDO_WINDOW {
+                return Err(CustomError::NothingToUndo.into());
+            }
+            if record.source_channel_deleted && !record.copies.is_empty() {
+                return Err(CustomError::UndoSourceChannelDeleted.into());
+            }
+            record.undoing = true;
+            let to_repost = record
+                .copies
+                .iter()
+                .filter(|(original, _)| record.deleted.contains(&original.id))
+                .cloned()
+                .collect::<Vec<_>>();
+            let undoing = (record.interaction_id, record.copies.len(), to_repost);
+            drop(records);
+            undoing
+        };
+
+        let undone = self.undo(user_id, interaction_id, &to_repost).await;
+        if undone.is_ok() {
+            let mut records = self.ctx.move_records.lock().unwrap();
+            if records
+                .get(&user_id)
+                .is_some_and(|record| record.interaction_id == interaction_id)
+            {
+                records.remove(&user_id);
+            }
+        } else {
+            self.ctx
+                .with_move_record(user_id, interaction_id, |record| record.undoing = false);
+        }
+        undone?;
+
+        self.handle
+            .reply(Reply::new().ephemeral().update_last().content(format!(
+                "Done! removed {copies_count} copies and put {} messages back",
+                to_repost.len()
+            )))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Puts the deleted originals back and deletes the copies, updating the
+    /// record as it goes
+    async fn undo(
+        &self,
+        user_id: Id<UserMarker>,
+        interaction_id: Id<InteractionMarker>,
+        to_repost: &[(Message, Message)],
+    ) -> Result<()> {
+        self.handle
+            .reply(Reply::new().ephemeral().update_last().content("Undoing the move..."))
+            .await?;
+
+        if let Some((first, _)) = to_repost.first() {
+            let source_channel = self
+                .ctx
+                .bot
+                .http
+                .channel(first.channel_id)
+                .await?
+                .model()
+                .await?;
+            let mut reposted = MovedCopies::new();
+            for (i, (original, copy)) in to_repost.iter().enumerate() {
+                // The attachments of the original are gone with it, the copy
+                // still has them
+                let attachments = self.ctx.download_attachments(copy).await?;
+                // Only the first part of a split copy has the content
+                let part;
+                let message = if reposted.contains_key(&original.id) {
+                    part = Message {
+                        content: String::new(),
+                        embeds: Vec::new(),
+                        reference: None,
+                        referenced_message: None,
+                        ..original.clone()
+                    };
+                    &part
+                } else {
+                    original
+                };
+                let repost = self
+                    .ctx
+                    .execute_webhook_as_member(message, &source_channel, &attachments, None, &reposted)
+                    .await?;
+                reposted.entry(original.id).or_insert_with(|| (&repost).into());
+                // Once its last part is back, the original isn't deleted anymore
+                if to_repost
+                    .get(i + 1)
+                    .is_none_or(|(next, _)| next.id != original.id)
+                {
+                    self.ctx.with_move_record(user_id, interaction_id, |record| {
+                        record.deleted.remove(&original.id);
+                    });
+                }
+            }
+        }
+
+        let (copies, created_channels) = self
+            .ctx
+            .with_move_record(user_id, interaction_id, |record| {
+                (
+                    record.copies.iter().map(|(_, copy)| copy.clone()).collect::<Vec<_>>(),
+                    record.created_channels.clone(),
+                )
+            })
+            .unwrap_or_default();
+
+        // The copies can be spread over a forum and the post created in it
+        let mut copies_by_channel: Vec<(Id<ChannelMarker>, Vec<Id<MessageMarker>>)> = Vec::new();
+        for copy in &copies {
+            // They go with the thread or post they're in
+            if created_channels.contains(&copy.channel_id) {
+                continue;
+            }
+            match copies_by_channel.last_mut() {
+                Some((channel_id, ids)) if *channel_id == copy.channel_id => ids.push(copy.id),
+                _ => copies_by_channel.push((copy.channel_id, vec![copy.id])),
+            }
+        }
+        for (channel_id, copy_ids) in &copies_by_channel {
+            for chunk in copy_ids.chunks(100) {
+                if let [copy_id] = chunk {
+                    self.ctx
+                        .bot
+                        .http
+                        .delete_message(*channel_id, *copy_id)
+                        .await?;
+                } else {
+                    self.ctx
+                        .bot
+                        .http
+                        .delete_messages(*channel_id, chunk)?
+                        .await?;
+                }
+                self.ctx.with_move_record(user_id, interaction_id, |record| {
+                    record.copies.retain(|(_, copy)| !chunk.contains(&copy.id));
+                });
+            }
+        }
+
+        for channel_id in &created_channels {
+            self.ctx.bot.http.delete_channel(*channel_id).await?;
+            self.ctx.with_move_record(user_id, interaction_id, |record| {
+                record.created_channels.retain(|id| id != channel_id);
+                record.copies.retain(|(_, copy)| copy.channel_id != *channel_id);
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Context {
+    /// Lets the move started by `interaction_id` be undone, now that it's done
+    pub fn finish_move_record(&self, user_id: Id<UserMarker>, interaction_id: Id<InteractionMarker>) {
+        self.with_move_record(user_id, interaction_id, |record| {
+            record.finished_at = Some(Instant::now());
+        });
+    }
+
+    fn with_move_record<T>(
+        &self,
+        user_id: Id<UserMarker>,
+        interaction_id: Id<InteractionMarker>,
+        f: impl FnOnce(&mut MoveRecord) -> T,
+    ) -> Option<T> {
+        self.move_records
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .filter(|record| record.interaction_id == interaction_id)
+            .map(f)
+    }
+}
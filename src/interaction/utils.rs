@@ -1,62 +1,39 @@
 use crate::interaction::InteractionContext;
-use crate::message;
+use crate::CustomError;
+use crate::extras;
+use crate::MessageInteractError::NoRange;
+use crate::job::{DeleteOld, HiddenChannel, KeepReactions, MoveJob, MoveOptions, MoveOutcome};
+use crate::message::{self, forum_tag_ids, MovedCopies};
+use sparkle_convenience::error::IntoError;
 use sparkle_convenience::reply::Reply;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 use tokio::time::Instant;
-use twilight_model::channel::message::MessageFlags;
-use twilight_model::channel::{Channel, Message};
-use twilight_model::guild::Permissions;
-use twilight_model::http::permission_overwrite::{PermissionOverwrite, PermissionOverwriteType};
-use twilight_model::id::marker::{ChannelMarker, GuildMarker, MessageMarker, RoleMarker};
+use twilight_model::channel::{Channel, ChannelType, Message};
+use twilight_model::id::marker::{ChannelMarker, GuildMarker, MessageMarker};
 use twilight_model::id::Id;
 
+/// Leaves room for the "and more" line under the 2000 characters of a
+/// message
+const REPORT_MAX_LENGTH: usize = 1900;
+
 impl InteractionContext<'_> {
     pub async fn bulk_delete(
         &self,
         messages: Vec<Message>,
         guild_id: Option<Id<GuildMarker>>,
     ) -> anyhow::Result<()> {
-        let mut messages = messages;
-
-        while (!messages.is_empty()) {
-            if (SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs()
-                - u64::try_from(messages[0].timestamp.as_secs())?)
-                > 2 * 7 * 24 * 60 * 60
-                || messages.len() == 1
-            {
-                for (idx, message) in messages.iter().enumerate() {
-                    if (idx + 1) % 10 == 0 && guild_id.as_ref().is_some() {
-                        println!(
-                            "deleting messages in {}: {}/{}",
-                            guild_id.as_ref().unwrap(),
-                            idx + 1,
-                            messages.len()
-                        );
-                    }
-
-                    self.ctx
-                        .bot
-                        .http
-                        .delete_message(message.channel_id, message.id)
-                        .await?;
+        let Some(channel_id) = messages.first().map(|message| message.channel_id) else {
+            return Ok(());
+        };
 
-                    tokio::time::sleep(Duration::from_millis(200)).await;
-                }
-            } else {
-                self.ctx
-                    .bot
-                    .http
-                    .delete_messages(
-                        messages[0].channel_id,
-                        &messages
-                            .drain(..messages.len().min(100))
-                            .map(|message| message.id)
-                            .collect::<Vec<_>>(),
-                    )?
-                    .await?;
-            }
-        }
-        Ok(())
+        self.ctx
+            .bulk_delete(
+                channel_id,
+                messages.iter().map(|message| message.id).collect(),
+                guild_id,
+                |deleted| self.record_deleted(deleted),
+            )
+            .await
     }
 
     pub async fn get_message_borned(
@@ -86,13 +63,22 @@ impl InteractionContext<'_> {
         if (!to.is_some() || from.unwrap() != to.unwrap()) {
             loop {
                 last_message_id = messages.last().map(|m| m.id);
-                let mut channel_messages;
-                let request = self.ctx.bot.http.channel_messages(channel).limit(100)?;
-                channel_messages = request
-                    .after(last_message_id.unwrap())
-                    .await?
-                    .model()
+                let response = self
+                    .ctx
+                    .pacer
+                    .run(|| async {
+                        Ok(self
+                            .ctx
+                            .bot
+                            .http
+                            .channel_messages(channel)
+                            .limit(100)?
+                            .after(last_message_id.unwrap())
+                            .await?)
+                    })
                     .await?;
+                self.ctx.pacer.observe(&response).await;
+                let mut channel_messages = response.model().await?;
 
                 if channel_messages.is_empty() {
                     break;
@@ -103,11 +89,77 @@ impl InteractionContext<'_> {
                     break;
                 }
                 messages.extend(channel_messages);
-                tokio::time::sleep(Duration::from_millis(100)).await;
             }
         }
         Ok(messages)
     }
+
+    /// The messages of `channel` sent between `after` and `before`, Unix
+    /// timestamps in seconds, oldest first
+    pub async fn get_messages_in_window(
+        &self,
+        channel: Id<ChannelMarker>,
+        after: i64,
+        before: Option<i64>,
+    ) -> anyhow::Result<Vec<Message>> {
+        let before = before.map(message::snowflake_at);
+        let mut last_message_id = message::snowflake_at(after);
+        let mut messages = Vec::new();
+
+        loop {
+            let response = self
+                .ctx
+                .pacer
+                .run(|| async {
+                    Ok(self
+                        .ctx
+                        .bot
+                        .http
+                        .channel_messages(channel)
+                        .limit(100)?
+                        .after(last_message_id)
+                        .await?)
+                })
+                .await?;
+            self.ctx.pacer.observe(&response).await;
+            let mut channel_messages = response.model().await?;
+            channel_messages.reverse();
+
+            let Some(last) = channel_messages.last() else {
+                break;
+            };
+            last_message_id = last.id;
+            if let Some(end) = channel_messages
+                .iter()
+                .position(|message| before.is_some_and(|before| message.id >= before))
+            {
+                messages.extend(channel_messages.drain(..end));
+                break;
+            }
+            messages.extend(channel_messages);
+        }
+
+        Ok(messages)
+    }
+
+    /// The messages from `from` to `to`, or sent in `window` when no message
+    /// is given
+    pub async fn get_message_range(
+        &self,
+        channel: Id<ChannelMarker>,
+        from: Option<Id<MessageMarker>>,
+        to: Option<Id<MessageMarker>>,
+        window: (Option<i64>, Option<i64>),
+    ) -> anyhow::Result<Vec<Message>> {
+        match (from, window) {
+            (Some(from), _) => self.get_message_borned(channel, from, to).await,
+            (None, (Some(after), before)) => {
+                self.get_messages_in_window(channel, after, before).await
+            }
+            (None, (None, _)) => Err(anyhow::anyhow!(NoRange)),
+        }
+    }
+
     pub async fn get_all_messages_from_beginning(
         &self,
         id: Id<ChannelMarker>,
@@ -116,19 +168,25 @@ impl InteractionContext<'_> {
         let mut last_message_id = None;
 
         loop {
-            let channel_messages;
-            let request = self.ctx.bot.http.channel_messages(id).limit(100)?;
-            if let Some(last_id) = last_message_id {
-                channel_messages = request.before(last_id).await?.model().await?;
-            } else {
-                channel_messages = request.await?.model().await?;
-            }
+            let response = self
+                .ctx
+                .pacer
+                .run(|| async {
+                    let request = self.ctx.bot.http.channel_messages(id).limit(100)?;
+                    Ok(if let Some(last_id) = last_message_id {
+                        request.before(last_id).await?
+                    } else {
+                        request.await?
+                    })
+                })
+                .await?;
+            self.ctx.pacer.observe(&response).await;
+            let channel_messages = response.model().await?;
             if channel_messages.is_empty() {
                 break;
             }
             messages.extend(channel_messages);
             last_message_id = messages.last().map(|m| m.id);
-            tokio::time::sleep(Duration::from_millis(100)).await;
         }
 
         messages.reverse();
@@ -144,49 +202,25 @@ impl InteractionContext<'_> {
         let message_id = message.id;
         let message_channel_id = message.channel_id;
 
-        // Check if the message has any attachments
-        if !message.attachments.is_empty() {
-            let mut http_attachments = Vec::new();
-
-            for channel_attachment in &message.attachments {
-                // Check if it has a spoiler
-                let filename = if let Some(flags) = message.flags {
-                    if flags.contains(MessageFlags::EPHEMERAL) {
-                        format!("SPOILER_{}", channel_attachment.filename)
-                    } else {
-                        channel_attachment.filename.clone()
-                    }
-                } else {
-                    channel_attachment.filename.clone()
-                };
-
-                let id = channel_attachment.id.into();
-
-                // Download the attachment content
-                let file_content = reqwest::get(&channel_attachment.url)
-                    .await?
-                    .bytes()
-                    .await?
-                    .to_vec();
-
-                let mut http_attachment = twilight_model::http::attachment::Attachment::from_bytes(
-                    filename,
-                    file_content,
-                    id,
-                );
-                // Check if the attachment has a description (alt)
-                if let Some(description) = &channel_attachment.description {
-                    http_attachment.description(description.clone());
-                }
-                http_attachments.push(http_attachment);
-            }
-            self.ctx
-                .execute_webhook_as_member(&message, &channel, &http_attachments)
-                .await?;
-        } else {
-            self.ctx
-                .execute_webhook_as_member(&message, &channel, &[])
-                .await?;
+        self.start_move_record();
+        let copy = self
+            .ctx
+            .copy_message(
+                &message,
+                &channel,
+                None,
+                &MovedCopies::new(),
+                self.ctx.large_attachments,
+                KeepReactions::Drop,
+            )
+            .await?;
+        self.record_copy(&message, &copy.messages);
+        if remove && copy.messages.is_empty() {
+            return Err(CustomError::CopyNotConfirmed.into());
+        }
+        // The files that weren't uploaded would be lost with the original
+        if remove && copy.files_left_out {
+            return Err(CustomError::FilesNotUploaded.into());
         }
         if (remove) {
             self.ctx
@@ -194,6 +228,7 @@ impl InteractionContext<'_> {
                 .http
                 .delete_message(message_channel_id, message_id)
                 .await?;
+            self.record_deleted(&[message_id]);
         }
     
         Ok(())
@@ -210,45 +245,101 @@ impl InteractionContext<'_> {
         self.handle.reply(Reply::new().content(reply_content)).await.unwrap();
     }
     
-    pub async fn move_messages(&self, messages: &Vec<Message>, result_channel: &Channel, guild_id: Id<GuildMarker>, hide_channel: Option<bool>)-> anyhow::Result<()> {
-        let mut hide_channel = hide_channel.unwrap_or(true);
-        let mut role_id : Option<Id<RoleMarker>> = None;
-        let total = messages.len();
-        let mut last_update = Instant::now();
-        let update_interval = Duration::from_millis(4000);
-        if(hide_channel){
-            hide_channel = false;
-            let roles = self.ctx.bot.http.roles(guild_id).await?.model().await?;
-            for role in roles {
-                if role.name == "Hide" {
-                    hide_channel = true;
-                    role_id = Some(role.id);
-                    break;
-                }
-            }
+    /// Copies `messages` to `result_channel`, returning the ones Discord
+    /// confirmed the copy of and the ones that were left behind. `guild_id`
+    /// is the guild the command was used in.
+    pub async fn move_messages(&self, messages: &Vec<Message>, result_channel: &Channel, guild_id: Id<GuildMarker>, options: &MoveOptions)-> anyhow::Result<MoveOutcome> {
+        for message in messages {
+            message::check(message)?;
         }
+        let Some(first) = messages.first() else {
+            return Ok(MoveOutcome::default());
+        };
+
+        // Fail before anything is sent if a tag doesn't exist
+        let post_tag_ids = if result_channel.kind == ChannelType::GuildForum {
+            forum_tag_ids(result_channel, &options.post_tags)?
+        } else {
+            Vec::new()
+        };
 
-        let mut hide_channel_permission_overwrite : Option<PermissionOverwrite> = None;
-
-        let mut show_channel_permission_overwrite : Option<PermissionOverwrite>= None;
-        if hide_channel {
-            hide_channel_permission_overwrite = Some(PermissionOverwrite{
-                allow: None,
-                deny: Some(Permissions::VIEW_CHANNEL),
-                id: role_id.unwrap().cast(),
-                kind: PermissionOverwriteType::Role,
-            });
-            show_channel_permission_overwrite = Some(PermissionOverwrite{
-                allow: Some(Permissions::VIEW_CHANNEL),
-                deny: None,
-                id: role_id.unwrap().cast(),
-                kind: PermissionOverwriteType::Role,
-            });
-            self.ctx.bot.http.update_channel_permission(result_channel.id, &hide_channel_permission_overwrite.unwrap()).await?;
+        // Discord only checked the user's permissions in this guild, a link can
+        // point to a channel of another one
+        let user_id = self.interaction.author_id().ok()?;
+        let source_channel = self.ctx.bot.http.channel(first.channel_id).await?.model().await?;
+        let source_guild_id = source_channel.guild_id.unwrap_or(guild_id);
+        if source_guild_id != guild_id {
+            self.ctx
+                .check_source(&source_channel, user_id, options.delete_old != DeleteOld::Nothing)
+                .await?;
         }
-        for message in messages {
-            message::check(message)?;
+        let target_guild_id = result_channel.guild_id.unwrap_or(guild_id);
+        if target_guild_id != guild_id {
+            self.ctx.check_target(result_channel, user_id).await?;
+        }
+
+        // Threads can't have permission overwrites, their parent is hidden
+        let hidden_channel_id = if result_channel.kind.is_thread() {
+            result_channel.parent_id.ok()?
+        } else {
+            result_channel.id
+        };
+        let hidden = if options.hide_channel {
+            self.ctx
+                .find_hide_role(target_guild_id)
+                .await?
+                .map(|role_id| HiddenChannel {
+                    channel_id: hidden_channel_id,
+                    role_id,
+                })
+        } else {
+            None
+        };
+        let mut job = MoveJob {
+            id: self.interaction.id,
+            guild_id: source_guild_id,
+            source_channel: first.channel_id,
+            target_channel: result_channel.id,
+            message_ids: messages.iter().map(|message| message.id).collect(),
+            cursor: None,
+            options: options.clone(),
+            post_id: None,
+            post_tag_ids,
+            hidden,
+            copies: MovedCopies::new(),
+            failed: Vec::new(),
+            pinned: self.ctx.pinned_ids(first.channel_id).await?,
+            saved_at: None,
+        };
+        self.ctx.jobs.checkpoint(&mut job).await?;
+        if let Some(hidden) = hidden {
+            self.ctx
+                .set_channel_hidden(hidden.channel_id, hidden.role_id, true)
+                .await?;
         }
+        self.start_move_record();
+
+        let result = self.copy_messages(messages, result_channel, &mut job).await;
+        let unpinned = if result.is_ok() {
+            self.ctx.restore_pins(&job).await
+        } else {
+            Vec::new()
+        };
+        self.ctx.finish_job(&job).await?;
+
+        result.map(|()| MoveOutcome {
+            copied: job.copied(),
+            failed: job.failed,
+            unpinned,
+            target_guild_id: Some(target_guild_id),
+        })
+    }
+
+    async fn copy_messages(&self, messages: &[Message], result_channel: &Channel, job: &mut MoveJob) -> anyhow::Result<()> {
+        let total = messages.len();
+        let mut last_update = Instant::now();
+        let update_interval = Duration::from_millis(4000);
+        let mut target = result_channel.clone();
 
         for (idx, message) in messages.iter().enumerate() {
             if last_update.elapsed() >= update_interval || idx == total - 1 {
@@ -258,70 +349,86 @@ impl InteractionContext<'_> {
 
             if (idx + 1) % 10 == 0 {
                 println!(
-                    "moving messages in {guild_id}: {}/{}",
+                    "moving messages in {}: {}/{}",
+                    job.guild_id,
                     idx + 1,
                     messages.len()
                 );
             }
 
-            // Check if the message has any attachments
-            if !message.attachments.is_empty() {
-                let mut http_attachments = Vec::new();
-                println!("Found {} attachments", message.attachments.len());
-                for channel_attachment in &message.attachments {
-                    // Check if it has a spoiler
-                    let filename = if let Some(flags) = message.flags {
-                        if flags.contains(MessageFlags::EPHEMERAL) {
-                            format!("SPOILER_{}", channel_attachment.filename)
-                        } else {
-                            channel_attachment.filename.clone()
-                        }
-                    } else {
-                        channel_attachment.filename.clone()
-                    };
-
-                    let id = channel_attachment.id.into();
-
-                    // Download the attachment content
-                    let file_content = reqwest::get(&channel_attachment.url)
-                        .await?
-                        .bytes()
-                        .await?
-                        .to_vec();
-                    println!(
-                        "Downloaded {} bytes from {}",
-                        file_content.len(),
-                        channel_attachment.url
-                    );
-                    let mut http_attachment =
-                        twilight_model::http::attachment::Attachment::from_bytes(
-                            filename,
-                            file_content,
-                            id,
-                        );
-                    // Check if the attachment has a description (alt)
-                    if let Some(description) = &channel_attachment.description {
-                        http_attachment.description(description.clone());
-                    }
-                    http_attachments.push(http_attachment);
-                }
+            let copies = self.ctx.copy_job_message(job, message, &mut target).await?;
+            self.record_copy(message, &copies);
+        }
 
-                self.ctx
-                    .execute_webhook_as_member(&message, &result_channel, &http_attachments)
-                    .await?;
-            } else {
-                // Send the message content
-                self.ctx
-                    .execute_webhook_as_member(&message, &result_channel, &[])
-                    .await?;
-            }
-            tokio::time::sleep(Duration::from_millis(200)).await;
+        Ok(())
+    }
+
+    /// Says the move is done, listing the messages that were left behind
+    /// and the pins that couldn't be restored
+    pub async fn reply_move_report(
+        &self,
+        guild_id: Id<GuildMarker>,
+        messages: &[Message],
+        outcome: &MoveOutcome,
+    ) -> anyhow::Result<()> {
+        let mut entries = Vec::new();
+        if !outcome.failed.is_empty() {
+            entries.push(format!(
+                "{} messages couldn't be moved and were left where they were:",
+                outcome.failed.len()
+            ));
+        }
+        for skipped in &outcome.failed {
+            let link = messages
+                .iter()
+                .find(|message| message.id == skipped.message_id)
+                .map_or_else(|| skipped.message_id.to_string(), |message| message_link(Some(guild_id), message));
+            entries.push(format!("- {link}: {}", skipped.reason));
         }
-        if hide_channel {
-            self.ctx.bot.http.update_channel_permission(result_channel.id, &show_channel_permission_overwrite.unwrap()).await?;
+        if !outcome.unpinned.is_empty() {
+            entries.push(format!(
+                "{} moved messages were pinned but their copy couldn't be:",
+                outcome.unpinned.len()
+            ));
         }
+        let target_guild_id = outcome.target_guild_id.unwrap_or(guild_id);
+        for unpinned in &outcome.unpinned {
+            entries.push(format!(
+                "- https://discord.com/channels/{target_guild_id}/{}/{}: {}",
+                unpinned.copy.channel_id, unpinned.copy.message_id, unpinned.reason
+            ));
+        }
+        let voice_messages = messages
+            .iter()
+            .filter(|message| extras::is_voice_message(message) && outcome.copied.contains(&message.id))
+            .count();
+        if voice_messages > 0 {
+            entries.push(format!(
+                "{voice_messages} voice messages were copied as audio files, webhooks can't send voice messages"
+            ));
+        }
+
+        let content = if entries.is_empty() {
+            "Done!".to_owned()
+        } else {
+            let mut lines = vec!["Done, but:".to_owned()];
+            let mut length = lines[0].len();
+            for (idx, entry) in entries.iter().enumerate() {
+                length += entry.len() + 1;
+                if length > REPORT_MAX_LENGTH {
+                    lines.push(format!("...and {} more", entries.len() - idx));
+                    break;
+                }
+                lines.push(entry.clone());
+            }
+            lines.join("\n")
+        };
+
+        self.handle.reply(Reply::new().ephemeral().update_last().content(content)).await?;
+
         Ok(())
     }
+
     pub async fn show_progress(&self, current: usize, total: usize) -> anyhow::Result<()> {
         let progress_bar = create_progress_bar(current, total);
 
@@ -336,6 +443,27 @@ impl InteractionContext<'_> {
     }
 }
 
+/// The messages of `outcome` that were copied, the only ones that can be
+/// deleted
+pub fn copied_only(messages: &[Message], outcome: &MoveOutcome) -> Vec<Message> {
+    messages
+        .iter()
+        .filter(|message| outcome.copied.contains(&message.id))
+        .cloned()
+        .collect()
+}
+
+pub fn message_link(guild_id: Option<Id<GuildMarker>>, message: &Message) -> String {
+    format!(
+        "https://discord.com/channels/{}/{}/{}",
+        guild_id
+            .or(message.guild_id)
+            .map_or_else(|| "@me".to_owned(), |id| id.to_string()),
+        message.channel_id,
+        message.id
+    )
+}
+
 fn create_progress_bar(progress: usize, total: usize) -> String {
     const BAR_LENGTH: usize = 40;
     let filled = (progress * BAR_LENGTH) / total;
@@ -0,0 +1,682 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use twilight_http::error::ErrorType;
+use twilight_model::{
+    channel::{Channel, ChannelType, Message},
+    guild::Permissions,
+    http::permission_overwrite::{PermissionOverwrite, PermissionOverwriteType},
+    id::{
+        marker::{ChannelMarker, GuildMarker, InteractionMarker, MessageMarker, RoleMarker, TagMarker},
+        Id,
+    },
+};
+
+use crate::{
+    attachments::{LargeAttachments, PlannedFile},
+    extras,
+    message::{default_thread_name, truncate_thread_name, MovedCopies},
+    pins::UnpinnedCopy,
+    Context, CustomError,
+};
+
+/// Where unfinished moves are written when `JOBS_DIR` isn't set
+const DEFAULT_JOBS_DIR: &str = "jobs";
+
+/// How long to wait before copying a message again, longer with each retry
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// How often a running move is saved, the messages copied since the last save
+/// are copied again if the bot restarts
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What to do with the original messages once they're all copied
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum DeleteOld {
+    #[default]
+    Nothing,
+    Messages,
+    Channel,
+}
+
+/// What to do when a message can't be copied
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum OnFailure {
+    /// Stop the move, nothing is deleted
+    #[default]
+    Abort,
+    /// Leave the message where it is and go on with the next one
+    Skip,
+    /// Try again this many times, then skip the message
+    Retry(u32),
+}
+
+impl OnFailure {
+    /// Waits before the next attempt and counts it in `attempts`, unless all
+    /// the retries were used
+    async fn wait_retry(self, attempts: &mut u32) -> bool {
+        match self {
+            Self::Retry(retries) if *attempts < retries => {
+                *attempts += 1;
+                tokio::time::sleep(RETRY_DELAY * *attempts).await;
+                true
+            }
+            Self::Abort | Self::Skip | Self::Retry(_) => false,
+        }
+    }
+}
+
+/// What to do with the reactions of the moved messages
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum KeepReactions {
+    /// The copies have no reactions
+    #[default]
+    Drop,
+    /// Add a line with each emoji and its count to the copies
+    Summary,
+    /// Same as `Summary`, also listing the first users who reacted
+    SummaryWithUsers,
+    /// React to the copies with the same emojis as the bot
+    React,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MoveOptions {
+    /// Hide the target channel from the "Hide" role while moving
+    pub hide_channel: bool,
+    pub delete_old: DeleteOld,
+    /// The name of the post to create when moving to a forum, the first
+    /// message is used if it's not set
+    pub post_title: Option<String>,
+    /// The names of the tags of the post to create when moving to a forum
+    pub post_tags: Vec<String>,
+    #[serde(default)]
+    pub on_failure: OnFailure,
+    #[serde(default)]
+    pub reactions: KeepReactions,
+    /// What to do with the attachments too large for the target guild,
+    /// `LARGE_ATTACHMENTS` if it's not set
+    #[serde(default)]
+    pub large_attachments: Option<LargeAttachments>,
+}
+
+impl Default for MoveOptions {
+    fn default() -> Self {
+        Self {
+            hide_channel: true,
+            delete_old: DeleteOld::Nothing,
+            post_title: None,
+            post_tags: Vec::new(),
+            on_failure: OnFailure::Abort,
+            reactions: KeepReactions::Drop,
+            large_attachments: None,
+        }
+    }
+}
+
+/// A channel the "Hide" role can't see until the job is done
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HiddenChannel {
+    pub channel_id: Id<ChannelMarker>,
+    pub role_id: Id<RoleMarker>,
+}
+
+/// A message that couldn't be copied, it's left where it was
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FailedMessage {
+    pub message_id: Id<MessageMarker>,
+    pub reason: String,
+}
+
+/// What became of the messages of a move
+#[derive(Clone, Debug, Default)]
+pub struct MoveOutcome {
+    /// The messages Discord confirmed the copy of, the only ones that can be
+    /// deleted
+    pub copied: Vec<Id<MessageMarker>>,
+    /// The messages that were left where they were
+    pub failed: Vec<FailedMessage>,
+    /// The copies of pinned messages that couldn't be pinned
+    pub unpinned: Vec<UnpinnedCopy>,
+    /// Where the copies are, when it's known
+    pub target_guild_id: Option<Id<GuildMarker>>,
+}
+
+/// The copy of a message, more than one message when its attachments were
+/// split
+#[derive(Debug, Default)]
+pub struct MessageCopy {
+    pub messages: Vec<Message>,
+    /// Whether some files were linked or left out instead of uploaded, they
+    /// would be lost with the original
+    pub files_left_out: bool,
+    /// The lines added to the content for the files and stickers that
+    /// weren't uploaded, an edit of the copy has to keep them
+    pub notes: Vec<String>,
+}
+
+/// A move in progress, saved every few seconds so that it can be picked up
+/// again if the bot restarts
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MoveJob {
+    pub id: Id<InteractionMarker>,
+    pub guild_id: Id<GuildMarker>,
+    pub source_channel: Id<ChannelMarker>,
+    pub target_channel: Id<ChannelMarker>,
+    pub message_ids: Vec<Id<MessageMarker>>,
+    /// The last message that was copied
+    pub cursor: Option<Id<MessageMarker>>,
+    pub options: MoveOptions,
+    /// The post created when the target is a forum
+    pub post_id: Option<Id<ChannelMarker>>,
+    /// The tags of the post, checked against the forum before the move starts
+    #[serde(default)]
+    pub post_tag_ids: Vec<Id<TagMarker>>,
+    pub hidden: Option<HiddenChannel>,
+    /// Where the messages copied so far went, so that replies link to the
+    /// copies
+    #[serde(default)]
+    pub copies: MovedCopies,
+    /// The messages that were skipped or whose copy wasn't confirmed, they
+    /// must not be deleted
+    #[serde(default)]
+    pub failed: Vec<FailedMessage>,
+    /// The messages pinned in the source channel when the move started, their
+    /// copies are pinned once everything is copied
+    #[serde(default)]
+    pub pinned: Vec<Id<MessageMarker>>,
+    /// When the job was last saved
+    #[serde(skip)]
+    pub saved_at: Option<Instant>,
+}
+
+impl MoveJob {
+    /// The messages that weren't copied yet
+    pub fn remaining(&self) -> &[Id<MessageMarker>] {
+        let copied = self.cursor.map_or(0, |cursor| {
+            self.message_ids
+                .iter()
+                .position(|id| *id == cursor)
+                .map_or(0, |idx| idx + 1)
+        });
+
+        &self.message_ids[copied..]
+    }
+
+    /// The messages Discord confirmed the copy of, the only ones that can
+    /// be deleted unless they failed anyway
+    pub fn copied(&self) -> Vec<Id<MessageMarker>> {
+        self.message_ids
+            .iter()
+            .filter(|id| {
+                self.copies.contains_key(id) && !self.failed.iter().any(|failed| failed.message_id == **id)
+            })
+            .copied()
+            .collect()
+    }
+}
+
+pub struct JobStore {
+    dir: PathBuf,
+}
+
+impl JobStore {
+    pub fn from_env() -> Self {
+        Self {
+            dir: env::var("JOBS_DIR")
+                .map_or_else(|_| PathBuf::from(DEFAULT_JOBS_DIR), PathBuf::from),
+        }
+    }
+
+    fn path(&self, id: Id<InteractionMarker>) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    pub async fn save(&self, job: &MoveJob) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        // Write then rename so that a crash never leaves half a job behind
+        let path = self.path(job.id);
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_vec(job)?).await?;
+        tokio::fs::rename(tmp_path, path).await?;
+
+        Ok(())
+    }
+
+    /// Saves `job` if it wasn't saved in the last [`SAVE_INTERVAL`]
+    pub async fn checkpoint(&self, job: &mut MoveJob) -> Result<()> {
+        if job
+            .saved_at
+            .is_some_and(|saved_at| saved_at.elapsed() < SAVE_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.save(job).await?;
+        job.saved_at = Some(Instant::now());
+
+        Ok(())
+    }
+
+    pub async fn remove(&self, id: Id<InteractionMarker>) -> Result<()> {
+        match tokio::fs::remove_file(self.path(id)).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads every saved job, a file that can't be read doesn't keep the
+    /// others from loading
+    pub async fn load_all(&self) -> Result<Vec<(PathBuf, Result<MoveJob>)>> {
+        if !Path::new(&self.dir).exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut jobs = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|extension| extension == "json") {
+                let job = match tokio::fs::read(&path).await {
+                    Ok(bytes) => serde_json::from_slice(&bytes).map_err(Into::into),
+                    Err(err) => Err(err.into()),
+                };
+                jobs.push((path, job));
+            }
+        }
+
+        Ok(jobs)
+    }
+}
+
+impl Context {
+    pub async fn find_hide_role(&self, guild_id: Id<GuildMarker>) -> Result<Option<Id<RoleMarker>>> {
+        let roles = self.bot.http.roles(guild_id).await?.model().await?;
+
+        Ok(roles
+            .into_iter()
+            .find(|role| role.name == "Hide")
+            .map(|role| role.id))
+    }
+
+    pub async fn set_channel_hidden(
+        &self,
+        channel_id: Id<ChannelMarker>,
+        role_id: Id<RoleMarker>,
+        hidden: bool,
+    ) -> Result<()> {
+        let (allow, deny) = if hidden {
+            (None, Some(Permissions::VIEW_CHANNEL))
+        } else {
+            (Some(Permissions::VIEW_CHANNEL), None)
+        };
+        self.bot
+            .http
+            .update_channel_permission(
+                channel_id,
+                &PermissionOverwrite {
+                    allow,
+                    deny,
+                    id: role_id.cast(),
+                    kind: PermissionOverwriteType::Role,
+                },
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Copies `message` with its attachments, stickers and poll to `channel`,
+    /// creating a post named `post_title` or after the message if it's a
+    /// forum. The copy is split over several messages when its attachments
+    /// don't fit in one and `large_attachments` is `Split`. The summary of
+    /// the reactions is added last, when it's known how long the content is.
+    pub async fn copy_message(
+        &self,
+        message: &Message,
+        channel: &Channel,
+        post_title: Option<&str>,
+        moved: &MovedCopies,
+        large_attachments: LargeAttachments,
+        reactions: KeepReactions,
+    ) -> Result<MessageCopy> {
+        let (stickers, mut notes) = extras::download_stickers(message).await;
+        let files = message
+            .attachments
+            .iter()
+            .map(PlannedFile::Attachment)
+            .chain(stickers.iter().map(PlannedFile::Sticker))
+            .collect::<Vec<_>>();
+        let limit = self.upload_limit(&files, channel).await?;
+        let plan = large_attachments.plan(&files, limit);
+        let files_left_out = !plan.notes.is_empty();
+        notes.extend(plan.notes);
+        let mut groups = plan.groups.into_iter();
+
+        let attachments = self
+            .download_group(&groups.next().unwrap_or_default())
+            .await?;
+        let mut message = self.with_extras(message).await?;
+        add_notes(&mut message.content, &notes);
+        if matches!(reactions, KeepReactions::Summary | KeepReactions::SummaryWithUsers) {
+            message = self
+                .with_reactions_summary(&message, reactions == KeepReactions::SummaryWithUsers)
+                .await?;
+        }
+        let post_title = (channel.kind == ChannelType::GuildForum).then(|| {
+            post_title.map_or_else(|| default_thread_name(&message), truncate_thread_name)
+        });
+
+        let copy = Box::pin(self.execute_webhook_as_member(
+            &message,
+            channel,
+            &attachments,
+            post_title.as_deref(),
+            moved,
+        ))
+        .await?;
+        let mut groups = groups.peekable();
+        if groups.peek().is_none() {
+            return Ok(MessageCopy {
+                messages: vec![copy],
+                files_left_out,
+                notes,
+            });
+        }
+
+        // The other attachments follow the first message, in the post it
+        // created if it's a forum
+        let post;
+        let channel = if channel.kind == ChannelType::GuildForum {
+            post = self.bot.http.channel(copy.channel_id).await?.model().await?;
+            &post
+        } else {
+            channel
+        };
+        let part = Message {
+            content: String::new(),
+            embeds: Vec::new(),
+            reference: None,
+            referenced_message: None,
+            sticker_items: Vec::new(),
+            ..message
+        };
+        let mut copies = vec![copy];
+        for group in groups {
+            let attachments = self.download_group(&group).await?;
+            copies.push(
+                Box::pin(self.execute_webhook_as_member(&part, channel, &attachments, None, moved))
+                    .await?,
+            );
+        }
+
+        Ok(MessageCopy {
+            messages: copies,
+            files_left_out,
+            notes,
+        })
+    }
+
+    /// Copies the next message of `job` to `target`, a forum is replaced by
+    /// the post created by the first message. A message that can't be copied
+    /// is retried or skipped as `job` says, nothing is returned when it's
+    /// skipped.
+    pub async fn copy_job_message(
+        &self,
+        job: &mut MoveJob,
+        message: &Message,
+        target: &mut Channel,
+    ) -> Result<Vec<Message>> {
+        let mut attempts = 0;
+        let copy = loop {
+            let err = match self.try_copy_job_message(job, message, target).await {
+                Ok(copy) => break copy,
+                Err(err) => err,
+            };
+            if job.options.on_failure == OnFailure::Abort {
+                return Err(err);
+            }
+            if !job.options.on_failure.wait_retry(&mut attempts).await {
+                job.failed.push(FailedMessage {
+                    message_id: message.id,
+                    reason: err.to_string(),
+                });
+                job.cursor = Some(message.id);
+                self.jobs.checkpoint(job).await?;
+                return Ok(Vec::new());
+            }
+        };
+        let copies = copy.messages;
+
+        if let Some(copy) = copies.first().filter(|_| target.kind == ChannelType::GuildForum) {
+            // The tags only change how the post looks, it's still where the
+            // next messages go if they can't be applied
+            let mut attempts = 0;
+            while !job.post_tag_ids.is_empty() {
+                let Err(err) = self
+                    .bot
+                    .http
+                    .update_thread(copy.channel_id)
+                    .applied_tags(Some(&job.post_tag_ids))
+                    .await
+                else {
+                    break;
+                };
+                if job.options.on_failure == OnFailure::Abort {
+                    return Err(err.into());
+                }
+                if !job.options.on_failure.wait_retry(&mut attempts).await {
+                    self.bot
+                        .log(format!(
+                            "couldn't tag the post <#{}> of the move {}: {err:?}",
+                            copy.channel_id, job.id
+                        ))
+                        .await;
+                    break;
+                }
+            }
+            *target = forum_post(target, copy.channel_id);
+            job.post_id = Some(target.id);
+        }
+
+        if let (KeepReactions::React, Some(copy)) = (job.options.reactions, copies.first()) {
+            self.add_reactions(message, copy).await;
+        }
+        match copies.first() {
+            // The original is the only one left with the files that weren't
+            // uploaded, deleting it would lose them
+            Some(first) if copy.files_left_out && job.options.delete_old != DeleteOld::Nothing => {
+                job.copies.insert(message.id, first.into());
+                job.failed.push(FailedMessage {
+                    message_id: message.id,
+                    reason: CustomError::FilesNotUploaded.to_string(),
+                });
+            }
+            Some(first) => {
+                job.copies.insert(message.id, first.into());
+            }
+            // It may have been sent, but without a copy to show for it the
+            // original has to stay
+            None => job.failed.push(FailedMessage {
+                message_id: message.id,
+                reason: CustomError::CopyNotConfirmed.to_string(),
+            }),
+        }
+        job.cursor = Some(message.id);
+        self.jobs.checkpoint(job).await?;
+
+        Ok(copies)
+    }
+
+    /// Copies `message` once, as `job` says
+    async fn try_copy_job_message(
+        &self,
+        job: &MoveJob,
+        message: &Message,
+        target: &Channel,
+    ) -> Result<MessageCopy> {
+        self.copy_message(
+            message,
+            target,
+            job.options.post_title.as_deref(),
+            &job.copies,
+            job.options.large_attachments.unwrap_or(self.large_attachments),
+            job.options.reactions,
+        )
+        .await
+    }
+
+    /// Finishes the moves that were interrupted by a restart, or aborts them
+    /// if `RESUME_JOBS` is `false`
+    pub async fn resume_jobs(&self) {
+        let jobs = match self.jobs.load_all().await {
+            Ok(jobs) => jobs,
+            Err(err) => {
+                self.bot.log(format!("couldn't load the saved jobs: {err:?}")).await;
+                return;
+            }
+        };
+        let resume = env::var("RESUME_JOBS").map_or(true, |resume| resume != "false");
+
+        for (path, job) in jobs {
+            let job = match job {
+                Ok(job) => job,
+                Err(err) => {
+                    self.bot
+                        .log(format!("couldn't load the saved job {}: {err:?}", path.display()))
+                        .await;
+                    continue;
+                }
+            };
+            let result = if resume {
+                Box::pin(self.resume_job(&job)).await
+            } else {
+                Ok(())
+            };
+            if let Err(err) = result {
+                self.bot
+                    .log(format!("couldn't resume the move {}: {err:?}", job.id))
+                    .await;
+            }
+            if let Err(err) = self.finish_job(&job).await {
+                self.bot
+                    .log(format!("couldn't clean up the move {}: {err:?}", job.id))
+                    .await;
+            }
+        }
+    }
+
+    async fn resume_job(&self, job: &MoveJob) -> Result<()> {
+        self.bot
+            .log(format!(
+                "resuming the move {} in {}: {} messages left",
+                job.id,
+                job.guild_id,
+                job.remaining().len()
+            ))
+            .await;
+        let mut target_channel = self
+            .bot
+            .http
+            .channel(job.post_id.unwrap_or(job.target_channel))
+            .await?
+            .model()
+            .await?;
+
+        let mut job = job.clone();
+        for message_id in job.remaining().to_vec() {
+            match self.bot.http.message(job.source_channel, message_id).await {
+                Ok(response) => {
+                    let message = response.model().await?;
+                    self.copy_job_message(&mut job, &message, &mut target_channel)
+                        .await?;
+                }
+                // Deleted while the bot was down, nothing to copy
+                Err(err)
+                    if matches!(err.kind(), ErrorType::Response { status, .. } if status.get() == 404) =>
+                {
+                    job.cursor = Some(message_id);
+                    self.jobs.checkpoint(&mut job).await?;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        for unpinned in self.restore_pins(&job).await {
+            self.bot
+                .log(format!(
+                    "couldn't pin https://discord.com/channels/{}/{}/{} while resuming the move {}: {}",
+                    target_channel.guild_id.unwrap_or(job.guild_id),
+                    unpinned.copy.channel_id,
+                    unpinned.copy.message_id,
+                    job.id,
+                    unpinned.reason
+                ))
+                .await;
+        }
+        match job.options.delete_old {
+            DeleteOld::Nothing => {}
+            DeleteOld::Messages => {
+                self.bulk_delete(job.source_channel, job.copied(), Some(job.guild_id), |_| {})
+                    .await?;
+            }
+            // The messages that weren't copied would go with the channel
+            DeleteOld::Channel if job.copied().len() < job.message_ids.len() => {}
+            DeleteOld::Channel => {
+                self.bot.http.delete_channel(job.source_channel).await?;
+            }
+        }
+        for failed in &job.failed {
+            self.bot
+                .log(format!(
+                    "couldn't copy https://discord.com/channels/{}/{}/{} while resuming the move {}: {}",
+                    job.guild_id, job.source_channel, failed.message_id, job.id, failed.reason
+                ))
+                .await;
+        }
+        self.bot
+            .log(format!(
+                "resumed and finished the move {} from <#{}> to <#{}>",
+                job.id, job.source_channel, job.target_channel
+            ))
+            .await;
+
+        Ok(())
+    }
+
+    /// Shows the target channel again and forgets the job
+    pub async fn finish_job(&self, job: &MoveJob) -> Result<()> {
+        if let Some(hidden) = job.hidden {
+            self.set_channel_hidden(hidden.channel_id, hidden.role_id, false)
+                .await?;
+        }
+        self.jobs.remove(job.id).await
+    }
+}
+
+/// Adds `notes` on their own lines at the end of `content`
+pub fn add_notes(content: &mut String, notes: &[String]) {
+    if notes.is_empty() {
+        return;
+    }
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    content.push_str(&notes.join("\n"));
+}
+
+/// The post created in `forum` by the first copied message, Discord made it
+/// with what the forum had
+fn forum_post(forum: &Channel, post_id: Id<ChannelMarker>) -> Channel {
+    Channel {
+        id: post_id,
+        kind: ChannelType::PublicThread,
+        parent_id: Some(forum.id),
+        ..forum.clone()
+    }
+}
@@ -1,6 +1,11 @@
 #![warn(clippy::nursery, clippy::pedantic)]
 
-use std::{env, sync::Arc};
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use anyhow::Result;
 use futures::StreamExt;
@@ -18,16 +23,33 @@ use twilight_model::{
     gateway::event::Event,
     guild::Permissions,
     id::{
-        marker::{ChannelMarker, GuildMarker},
+        marker::{ChannelMarker, GuildMarker, UserMarker},
         Id,
     },
 };
 use twilight_standby::Standby;
 
-use crate::interaction::set_commands;
+use crate::{
+    attachments::LargeAttachments,
+    interaction::{move_range::RangeStart, set_commands, undo_move::MoveRecord},
+    job::JobStore,
+    message::{webhook_timeout_from_env, ChannelWebhook},
+    mirror::Mirrors,
+    pacing::Pacer,
+};
 
+mod archive;
+mod attachments;
+mod extras;
 mod interaction;
+mod job;
 mod message;
+mod mirror;
+mod pacing;
+mod permissions;
+mod pins;
+mod reactions;
+mod transcript;
 
 const TEST_GUILD_ID: Id<GuildMarker> = Id::new(1325055758504562718);
 const LOGGING_CHANNEL_ID: Id<ChannelMarker> = Id::new(1368913295347552367);
@@ -49,6 +71,36 @@ pub enum MessageInteractError{
     IdNotFoundLink,
     #[error("Can't be the same value")]
     SameValueForId,
+    #[error("There is no tag named {0} in this forum")]
+    UnknownForumTag(String),
+    #[error("Pick a post of the forum, not the forum itself")]
+    ForumNotPost,
+    #[error("Threads can't be created inside of a thread")]
+    ThreadInThread,
+    #[error("{0} isn't a valid regex")]
+    InvalidRegex(String),
+    #[error("{0} isn't a date I understand, use YYYY-MM-DD HH:MM (in UTC) or a duration like 2h")]
+    InvalidDate(String),
+    #[error("The after date must be earlier than the before date")]
+    AfterNotBeforeBefore,
+    #[error("Please send the first and last message links, or a time window with after")]
+    NoRange,
+    #[error("The archive is {0}, more than the {1} I can upload here, export fewer messages or without the attachments")]
+    ArchiveTooLarge(String, String),
+    #[error("This archive is too large to unzip, its files can be up to {0} each and {1} in total")]
+    ArchiveUnzipsTooLarge(String, String),
+    #[error("This file isn't an archive made by /export_messages")]
+    InvalidArchive,
+    #[error("This archive is version {0}, I can only read archives up to version {}", archive::ARCHIVE_VERSION)]
+    UnsupportedArchiveVersion(u32),
+    #[error("Pick the {0} from the list")]
+    NotPicked(String),
+    #[error("The messages must be in this server")]
+    NotInThisServer,
+    #[error("This channel is already mirrored there")]
+    MirrorExists,
+    #[error("This channel isn't mirrored there")]
+    NoMirror,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
@@ -65,6 +117,14 @@ pub enum CustomError {
         "you need **Send Messages** permissions in the channel you want to move the messages to"
     )]
     SendMessagesPermissionMissing,
+    #[error("you need to be able to read the messages you want to move")]
+    ReadMessagesPermissionMissing,
+    #[error("you need to be in the server you want to move the messages to")]
+    NotInTargetGuild,
+    #[error("you need **Manage Channels** permissions in the server you want to create the channel in")]
+    ManageChannelsPermissionMissing,
+    #[error("i need these permissions in the channel you want to move the messages to first:\n{0}")]
+    BotPermissionsMissing(String),
     #[error("one of the messages is too long, you're probably using your super nitro powers")]
     MessageTooLong,
     #[error(
@@ -82,18 +142,60 @@ pub enum CustomError {
          support server and tell lara"
     )]
     MessageTooOld,
+    #[error("there's nothing to undo, i only remember your last move for an hour")]
+    NothingToUndo,
+    #[error("your move isn't done yet, undo it once it's finished")]
+    MoveInProgress,
+    #[error("use *mark range start* on the first message of the range first, i remember it for 10 minutes")]
+    NoRangeStart,
+    #[error("the channel the messages came from was deleted, i can't put them back there")]
+    UndoSourceChannelDeleted,
+    #[error("discord didn't confirm the copy of the message, so i kept the original")]
+    CopyNotConfirmed,
+    #[error("some of the message's files were too large to be uploaded, so i kept the original")]
+    FilesNotUploaded,
+    #[error("discord took too long to answer when copying a message, so i kept the original")]
+    WebhookTimeout,
+    #[error("discord refused the copy of a message, so i kept the original")]
+    WebhookRejected,
+    #[error(
+        "i need **Manage Webhooks** and **Send Messages** permissions in the channel you want to \
+         move the messages to"
+    )]
+    WebhookMissingPermissions,
+    #[error("the channel you want to move the messages to doesn't exist anymore")]
+    WebhookUnknownChannel,
+    #[error("mirrors aren't enabled on this bot, whoever hosts it has to set ENABLE_MIRRORS first")]
+    MirrorsDisabled,
 }
 
 struct Context {
     bot: Bot,
     standby: Standby,
+    /// The last move of each user, for `/undo_move`
+    move_records: Mutex<HashMap<Id<UserMarker>, MoveRecord>>,
+    /// The range start each user marked, for `move range ending here`
+    range_starts: Mutex<HashMap<Id<UserMarker>, RangeStart>>,
+    jobs: JobStore,
+    mirrors: Mirrors,
+    /// The webhook of each channel messages were moved to
+    webhooks: Mutex<HashMap<Id<ChannelMarker>, ChannelWebhook>>,
+    pacer: Pacer,
+    large_attachments: LargeAttachments,
+    /// How long Discord has to answer a webhook execution
+    webhook_timeout: Duration,
 }
 
 impl Context {
     async fn handle_event(&self, event: Event) {
         self.standby.process(&event);
-        if let Event::InteractionCreate(interaction) = event {
-            self.handle_interaction(interaction.0).await;
+        match event {
+            Event::InteractionCreate(interaction) => self.handle_interaction(interaction.0).await,
+            Event::MessageCreate(message) => Box::pin(self.mirror_message(&message.0)).await,
+            Event::MessageUpdate(update) => self.mirror_update(&update).await,
+            Event::MessageDelete(delete) => self.mirror_delete(delete.channel_id, &[delete.id]).await,
+            Event::MessageDeleteBulk(delete) => self.mirror_delete(delete.channel_id, &delete.ids).await,
+            _ => {}
         }
     }
 }
@@ -101,12 +203,22 @@ impl Context {
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
-    let (mut bot, mut shards) = Bot::new(
-        env::var("BOT_TOKEN")?,
-        Intents::empty(),
-        EventTypeFlags::INTERACTION_CREATE,
-    )
-    .await?;
+    let mirrors = Mirrors::from_env()?;
+    // Only mirrors need to read messages, and the message content intent is
+    // privileged
+    let (intents, event_types) = if mirrors.enabled {
+        (
+            Intents::GUILD_MESSAGES | Intents::MESSAGE_CONTENT,
+            EventTypeFlags::INTERACTION_CREATE
+                | EventTypeFlags::MESSAGE_CREATE
+                | EventTypeFlags::MESSAGE_UPDATE
+                | EventTypeFlags::MESSAGE_DELETE
+                | EventTypeFlags::MESSAGE_DELETE_BULK,
+        )
+    } else {
+        (Intents::empty(), EventTypeFlags::INTERACTION_CREATE)
+    };
+    let (mut bot, mut shards) = Bot::new(env::var("BOT_TOKEN")?, intents, event_types).await?;
     bot.set_logging_format(DisplayFormat::Debug);
     bot.set_logging_channel(LOGGING_CHANNEL_ID).await?;
     bot.set_logging_file("logs.txt".to_owned());
@@ -116,6 +228,19 @@ async fn main() -> Result<()> {
     let ctx = Arc::new(Context {
         bot,
         standby: Standby::new(),
+        move_records: Mutex::new(HashMap::new()),
+        range_starts: Mutex::new(HashMap::new()),
+        jobs: JobStore::from_env(),
+        mirrors,
+        webhooks: Mutex::new(HashMap::new()),
+        pacer: Pacer::from_env(),
+        large_attachments: LargeAttachments::from_env(),
+        webhook_timeout: webhook_timeout_from_env(),
+    });
+
+    let resume_ctx = Arc::clone(&ctx);
+    tokio::spawn(async move {
+        resume_ctx.resume_jobs().await;
     });
 
     let mut events = ShardEventStream::new(shards.iter_mut());
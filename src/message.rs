@@ -1,13 +1,170 @@
+use crate::pacing::is_rate_limited;
+use crate::MessageInteractError::UnknownForumTag;
 use crate::{Context, CustomError};
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use sparkle_convenience::error::IntoError;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::time::timeout;
-use twilight_model::channel::{Channel, Message};
+use twilight_model::channel::message::Embed;
+use twilight_model::channel::{self, Channel, ChannelType, Message};
 use twilight_model::http::attachment;
+use twilight_http::api_error::{ApiError, GeneralApiError};
+use twilight_http::error::ErrorType;
+use twilight_model::id::marker::{ChannelMarker, GuildMarker, MessageMarker, TagMarker, WebhookMarker};
+use twilight_model::id::Id;
 use twilight_util::builder::embed::{EmbedAuthorBuilder, EmbedBuilder, EmbedFieldBuilder, EmbedFooterBuilder, ImageSource};
 
+/// The webhook name the bot looks for and creates in channels
+const WEBHOOK_NAME: &str = "interchannel message mover";
+
+/// What the name of a spoilered attachment starts with
+const SPOILER_PREFIX: &str = "SPOILER_";
+
+/// Discord's error code for a webhook that doesn't exist anymore
+const UNKNOWN_WEBHOOK_CODE: u64 = 10015;
+
+/// Discord's error code for a channel that doesn't exist anymore
+const UNKNOWN_CHANNEL_CODE: u64 = 10003;
+
+/// Discord's error code for a request the bot doesn't have the permissions
+/// for
+const MISSING_PERMISSIONS_CODE: u64 = 50013;
+
+/// A webhook the bot can execute
+#[derive(Clone, Debug)]
+pub struct ChannelWebhook {
+    pub id: Id<WebhookMarker>,
+    pub token: String,
+}
+
+/// Where a moved message was copied
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MovedCopy {
+    pub channel_id: Id<ChannelMarker>,
+    pub message_id: Id<MessageMarker>,
+}
+
+impl From<&Message> for MovedCopy {
+    fn from(copy: &Message) -> Self {
+        Self {
+            channel_id: copy.channel_id,
+            message_id: copy.id,
+        }
+    }
+}
+
+/// The copies of the messages moved so far, by original message id
+pub type MovedCopies = HashMap<Id<MessageMarker>, MovedCopy>;
+
+/// How long Discord has to answer a webhook execution when
+/// `WEBHOOK_TIMEOUT_SECS` isn't set
+const DEFAULT_WEBHOOK_TIMEOUT: Duration = Duration::from_mins(1);
+
+pub fn webhook_timeout_from_env() -> Duration {
+    env::var("WEBHOOK_TIMEOUT_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .filter(|secs| *secs > 0)
+        .map_or(DEFAULT_WEBHOOK_TIMEOUT, Duration::from_secs)
+}
+
+/// Explains why a webhook execution failed, the rate limits and deleted
+/// webhooks are left as they are so they can be retried
+fn webhook_error(err: twilight_http::Error) -> anyhow::Error {
+    if is_unknown_webhook(&err) || is_rate_limited(&err) {
+        return err.into();
+    }
+    let ErrorType::Response { error, status, .. } = err.kind() else {
+        return err.into();
+    };
+    let code = match error {
+        ApiError::General(GeneralApiError { code, .. }) => Some(*code),
+        _ => None,
+    };
+
+    let custom_err = match (status.get(), code) {
+        (_, Some(MISSING_PERMISSIONS_CODE)) | (403, _) => CustomError::WebhookMissingPermissions,
+        (_, Some(UNKNOWN_CHANNEL_CODE)) | (404, _) => CustomError::WebhookUnknownChannel,
+        (400 | 413, _) => CustomError::WebhookRejected,
+        _ => return err.into(),
+    };
+    anyhow::Error::new(err).context(custom_err)
+}
+
+const fn is_unknown_webhook(err: &twilight_http::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorType::Response {
+            error: ApiError::General(GeneralApiError { code, .. }),
+            ..
+        } if *code == UNKNOWN_WEBHOOK_CODE
+    )
+}
+
+/// Whether `err` is about a channel that was deleted, from fetching it or
+/// from executing its webhook
+pub fn is_unknown_channel(err: &anyhow::Error) -> bool {
+    if err.downcast_ref::<CustomError>() == Some(&CustomError::WebhookUnknownChannel) {
+        return true;
+    }
+    err.downcast_ref::<twilight_http::Error>().is_some_and(|err| {
+        matches!(
+            err.kind(),
+            ErrorType::Response {
+                error: ApiError::General(GeneralApiError { code, .. }),
+                ..
+            } if *code == UNKNOWN_CHANNEL_CODE
+        )
+    })
+}
+
 impl Context {
+    /// The webhook used to send messages in `channel_id`, only looked up or
+    /// created the first time
+    pub async fn channel_webhook(&self, channel_id: Id<ChannelMarker>) -> Result<ChannelWebhook> {
+        if let Some(webhook) = self.webhooks.lock().unwrap().get(&channel_id) {
+            return Ok(webhook.clone());
+        }
+
+        let webhook = match self
+            .bot
+            .http
+            .channel_webhooks(channel_id)
+            .await?
+            .models()
+            .await?
+            .into_iter()
+            .find(|webhook| webhook.token.is_some())
+        {
+            Some(webhook) => webhook,
+            None => {
+                self.bot
+                    .http
+                    .create_webhook(channel_id, WEBHOOK_NAME)?
+                    .await?
+                    .model()
+                    .await?
+            }
+        };
+        let webhook = ChannelWebhook {
+            id: webhook.id,
+            token: webhook.token.ok()?,
+        };
+        self.webhooks
+            .lock()
+            .unwrap()
+            .insert(channel_id, webhook.clone());
+
+        Ok(webhook)
+    }
+
+    pub fn forget_webhook(&self, channel_id: Id<ChannelMarker>) {
+        self.webhooks.lock().unwrap().remove(&channel_id);
+    }
+
     pub async fn execute_webhook_as_member_reference(
         &self,
         message: &Message,
@@ -84,31 +241,11 @@ impl Context {
         }
 
         // Send via webhook
-        let webhook = match self
-            .bot
-            .http
-            .channel_webhooks(channel_id)
-            .await?
-            .models()
-            .await?
-            .into_iter()
-            .find(|webhook| webhook.token.is_some())
-        {
-            Some(webhook) => webhook,
-            None => {
-                self.bot
-                    .http
-                    .create_webhook(channel_id, "interchannel message mover")?
-                    .await?
-                    .model()
-                    .await?
-            }
-        };
-        let webhook_token = webhook.token.ok()?;
+        let webhook = self.channel_webhook(channel_id).await?;
         let mut execute_webhook = self
             .bot
             .http
-            .execute_webhook(webhook.id, &webhook_token);
+            .execute_webhook(webhook.id, &webhook.token);
         execute_webhook = execute_webhook.attachments(attachments).expect("attachments");
         let str_payload = serde_json::to_string(&payload).ok();
         println!("{:?}", str_payload);
@@ -116,48 +253,64 @@ impl Context {
         if let Some(u8)=u8_payload{
             execute_webhook=execute_webhook.payload_json(u8);
         }
-        execute_webhook.await?;
+        if let Err(err) = execute_webhook.await {
+            if is_unknown_webhook(&err) {
+                self.forget_webhook(channel_id);
+            }
+            return Err(err.into());
+        }
         Ok(())
     }
+    /// Sends `message` to `channel` as its author, returning the copy.
+    /// Sending to a forum creates a post named `post_name`, a reply to a
+    /// message in `moved` links to its copy.
     pub async fn execute_webhook_as_member(
         &self,
         message: &Message,
         channel: &Channel,
         attachments: &[attachment::Attachment],
-    ) -> Result<()> {
-        let mut channel_id = channel.id;
-        let mut thread_id = None;
-        if channel.kind.is_thread() {
-            thread_id = Some(channel_id);
-            channel_id = channel.parent_id.ok()?;
+        post_name: Option<&str>,
+        moved: &MovedCopies,
+    ) -> Result<Message> {
+        let channel_id = if channel.kind.is_thread() {
+            channel.parent_id.ok()?
+        } else {
+            channel.id
         };
 
-        let webhook = match self
-            .bot
-            .http
-            .channel_webhooks(channel_id)
-            .await?
-            .models()
-            .await?
-            .into_iter()
-            .find(|webhook| webhook.token.is_some())
+        let webhook = self.channel_webhook(channel_id).await?;
+        match self
+            .pacer
+            .run(|| self.send_as_member(&webhook, message, channel, attachments, post_name, moved))
+            .await
         {
-            Some(webhook) => webhook,
-            None => {
-                self.bot
-                    .http
-                    .create_webhook(channel_id, "interchannel message mover")?
-                    .await?
-                    .model()
-                    .await?
+            // The webhook was deleted after it was cached
+            Err(err) if err.downcast_ref().is_some_and(is_unknown_webhook) => {
+                self.forget_webhook(channel_id);
+                let webhook = self.channel_webhook(channel_id).await?;
+                self.pacer
+                    .run(|| self.send_as_member(&webhook, message, channel, attachments, post_name, moved))
+                    .await
             }
-        };
-        let webhook_token = webhook.token.ok()?;
+            result => result,
+        }
+    }
+
+    async fn send_as_member(
+        &self,
+        webhook: &ChannelWebhook,
+        message: &Message,
+        channel: &Channel,
+        attachments: &[attachment::Attachment],
+        post_name: Option<&str>,
+        moved: &MovedCopies,
+    ) -> Result<Message> {
+        let thread_id = channel.kind.is_thread().then_some(channel.id);
 
         let mut execute_webhook = self
             .bot
             .http
-            .execute_webhook(webhook.id, &webhook_token)
+            .execute_webhook(webhook.id, &webhook.token)
             .attachments(attachments)
             .expect("attachments")
             .content(&message.content)
@@ -177,26 +330,7 @@ impl Context {
             }
             add_embed = true;
         }
-        if let Some(ref_msg) = message.reference.as_ref() {
-            
-            let msg = self.bot.http.message(ref_msg.channel_id.unwrap(), ref_msg.message_id.unwrap()).await?.model().await?;
-            let my_embed = EmbedBuilder::new()
-                .author(
-                    EmbedAuthorBuilder::new(msg.author.name)
-                        .icon_url(
-                            ImageSource::url(
-                                msg.author.avatar.map(|avatar| { format!("https://cdn.discordapp.com/avatars/{}/{}.png", message.author.id, avatar) }).unwrap()
-                            )?
-                        )
-                )
-                .footer(EmbedFooterBuilder::new(format!("{}", self.bot.http.channel(msg.channel_id).await?.model().await?.name.unwrap())))
-                .url(format!("https://discord.com/channels/{}/{}/{}", ref_msg.guild_id.map(|a| a.to_string()).unwrap_or("@me".to_string()), ref_msg.channel_id.unwrap(), ref_msg.message_id.unwrap()))
-                .field(
-                    EmbedFieldBuilder::new("Jump", format!("[Go to message](https://discord.com/channels/{}/{}/{})",ref_msg.guild_id.map(|a| a.to_string()).unwrap_or("@me".to_string()), ref_msg.channel_id.unwrap(), ref_msg.message_id.unwrap())).build()
-                )
-                .description(msg.content.clone())
-                .timestamp(msg.timestamp)
-                .build();
+        if let Some(my_embed) = self.reply_embed(message, channel, moved).await? {
             embed_array.push(my_embed);
             add_embed = true;
         }
@@ -206,43 +340,289 @@ impl Context {
         if let Some(thread_id) = thread_id {
             execute_webhook = execute_webhook.thread_id(thread_id);
         }
+        if let Some(post_name) = post_name.filter(|_| channel.kind == ChannelType::GuildForum) {
+            execute_webhook = execute_webhook.thread_name(post_name);
+        }
 
-        if let Some(avatar_url) = message
-            .member
-            .as_ref()
-            .and_then(|member| member.avatar)
-            .zip(message.guild_id)
-            .map(|(avatar, guild_id)| {
+        let avatar_url = avatar_url(message);
+        if let Some(avatar_url) = &avatar_url {
+            execute_webhook = execute_webhook.avatar_url(avatar_url);
+        }
+
+        let response = timeout(self.webhook_timeout, execute_webhook.wait())
+            .await
+            .map_err(|_| CustomError::WebhookTimeout)?
+            .map_err(webhook_error)?;
+        self.pacer.observe(&response).await;
+
+        Ok(response.model().await?)
+    }
+
+    /// The quote of the message `message` replies to, linking to its copy if
+    /// it's in `moved`
+    async fn reply_embed(
+        &self,
+        message: &Message,
+        channel: &Channel,
+        moved: &MovedCopies,
+    ) -> Result<Option<Embed>> {
+        let Some(ref_msg) = message.reference.as_ref() else {
+            return Ok(None);
+        };
+        // The referenced message is embedded when fetching history, only
+        // ask Discord for it if it's missing
+        let msg = match &message.referenced_message {
+            Some(referenced) => (**referenced).clone(),
+            None => self.bot.http.message(ref_msg.channel_id.unwrap(), ref_msg.message_id.unwrap()).await?.model().await?,
+        };
+        // Point to the copy of the replied message when it was moved too,
+        // the original is usually deleted
+        let (jump_guild, jump_channel, jump_message) = match moved.get(&msg.id) {
+            Some(copy) => (channel.guild_id.or(ref_msg.guild_id), copy.channel_id, copy.message_id),
+            None => (ref_msg.guild_id, msg.channel_id, msg.id),
+        };
+        let jump_url = format!(
+            "https://discord.com/channels/{}/{jump_channel}/{jump_message}",
+            jump_guild.map_or_else(|| "@me".to_owned(), |guild_id| guild_id.to_string())
+        );
+        let channel_name = if jump_channel == channel.id {
+            channel.name.clone()
+        } else {
+            self.bot.http.channel(jump_channel).await?.model().await?.name
+        };
+        let mut author = EmbedAuthorBuilder::new(msg.author.name);
+        if let Some(avatar) = msg.author.avatar {
+            author = author.icon_url(ImageSource::url(format!(
+                "https://cdn.discordapp.com/avatars/{}/{avatar}.png",
+                msg.author.id
+            ))?);
+        }
+        let mut my_embed = EmbedBuilder::new()
+            .author(author)
+            .url(&jump_url)
+            .field(EmbedFieldBuilder::new("Jump", format!("[Go to message]({jump_url})")).build())
+            .description(msg.content.clone())
+            .timestamp(msg.timestamp);
+        if let Some(channel_name) = channel_name {
+            my_embed = my_embed.footer(EmbedFooterBuilder::new(channel_name));
+        }
+
+        Ok(Some(my_embed.build()))
+    }
+
+    /// Deletes messages of `channel_id`, in bulk when they're recent enough,
+    /// calling `on_deleted` with the ids as they're deleted.
+    pub async fn bulk_delete(
+        &self,
+        channel_id: Id<ChannelMarker>,
+        message_ids: Vec<Id<MessageMarker>>,
+        guild_id: Option<Id<GuildMarker>>,
+        mut on_deleted: impl FnMut(&[Id<MessageMarker>]) + Send,
+    ) -> Result<()> {
+        let (mut one_by_one, recent): (Vec<_>, Vec<_>) = message_ids
+            .into_iter()
+            .partition(|id| is_outside_bulk_delete_window(*id));
+
+        for chunk in recent.chunks(100) {
+            if let [message_id] = chunk {
+                one_by_one.push(*message_id);
+                continue;
+            }
+            let response = self
+                .pacer
+                .run(|| async { Ok(self.bot.http.delete_messages(channel_id, chunk)?.await?) })
+                .await?;
+            self.pacer.observe(&response).await;
+            on_deleted(chunk);
+        }
+
+        for (idx, message_id) in one_by_one.iter().enumerate() {
+            if (idx + 1) % 10 == 0 && guild_id.as_ref().is_some() {
+                println!(
+                    "deleting messages in {}: {}/{}",
+                    guild_id.as_ref().unwrap(),
+                    idx + 1,
+                    one_by_one.len()
+                );
+            }
+
+            let response = self
+                .pacer
+                .run(|| async { Ok(self.bot.http.delete_message(channel_id, *message_id).await?) })
+                .await?;
+            self.pacer.observe(&response).await;
+            on_deleted(&[*message_id]);
+        }
+        Ok(())
+    }
+
+    /// Downloads the attachments of `message` so they can be uploaded again.
+    pub async fn download_attachments(
+        &self,
+        message: &Message,
+    ) -> Result<Vec<attachment::Attachment>> {
+        let mut http_attachments = Vec::new();
+        for channel_attachment in &message.attachments {
+            http_attachments.push(self.download_attachment(channel_attachment).await?);
+        }
+
+        Ok(http_attachments)
+    }
+
+    /// Downloads `channel_attachment` so it can be uploaded again. It's read
+    /// in chunks into a buffer of its size, but the whole file is held in
+    /// memory until it's sent, twilight can only upload attachments from
+    /// bytes.
+    ///
+    /// Discord works the size, duration and content type out of the file
+    /// itself, only the name, which marks spoilers, and the description have
+    /// to be kept.
+    pub async fn download_attachment(
+        &self,
+        channel_attachment: &channel::Attachment,
+    ) -> Result<attachment::Attachment> {
+        let mut response = reqwest::get(&channel_attachment.url)
+            .await?
+            .error_for_status()?;
+        let mut file_content = Vec::with_capacity(usize::try_from(channel_attachment.size)?);
+        while let Some(chunk) = response.chunk().await? {
+            file_content.extend_from_slice(&chunk);
+        }
+
+        let mut http_attachment = attachment::Attachment::from_bytes(
+            attachment_filename(channel_attachment),
+            file_content,
+            channel_attachment.id.into(),
+        );
+        // Check if the attachment has a description (alt)
+        if let Some(description) = &channel_attachment.description {
+            http_attachment.description(description.clone());
+        }
+
+        Ok(http_attachment)
+    }
+}
+
+/// The avatar of the author of `message`, the one they have in the guild if
+/// they set one
+pub fn avatar_url(message: &Message) -> Option<String> {
+    message
+        .member
+        .as_ref()
+        .and_then(|member| member.avatar)
+        .zip(message.guild_id)
+        .map(|(avatar, guild_id)| {
+            format!(
+                "https://cdn.discordapp.com/guilds/{guild_id}/users/{}/avatar/{}.png",
+                message.author.id, avatar
+            )
+        })
+        .or_else(|| {
+            message.author.avatar.map(|avatar| {
                 format!(
-                    "https://cdn.discordapp.com/guilds/{guild_id}/users/{}/avatar/{}.png",
+                    "https://cdn.discordapp.com/avatars/{}/{}.png",
                     message.author.id, avatar
                 )
             })
-            .or_else(|| {
-                message.author.avatar.map(|avatar| {
-                    format!(
-                        "https://cdn.discordapp.com/avatars/{}/{}.png",
-                        message.author.id, avatar
-                    )
-                })
-            })
-        {
-            match timeout(Duration::from_secs(60), execute_webhook.avatar_url(&avatar_url)).await{
-                Ok(_) => {
-                    return Ok(());
-                }
-                Err(_) => {
-                    println!("Failed to send webhook.");
-                }
-            }
-        } else {
-            execute_webhook.await?;
-        }
+        })
+}
 
-        Ok(())
+/// The name the author of `message` is shown with, their nickname or their
+/// global name if they have none
+pub fn display_name(message: &Message) -> String {
+    message
+        .member
+        .as_ref()
+        .and_then(|member| member.nick.clone())
+        .or_else(|| message.author.global_name.clone())
+        .unwrap_or_else(|| message.author.name.clone())
+}
+
+/// Messages older than this can't go through the bulk delete endpoint.
+const BULK_DELETE_MAX_AGE_SECS: u64 = 2 * 7 * 24 * 60 * 60;
+
+const DISCORD_EPOCH_MILLIS: u64 = 1_420_070_400_000;
+
+/// Whether `bulk_delete` will have to delete this message on its own.
+pub fn is_outside_bulk_delete_window(message_id: Id<MessageMarker>) -> bool {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs());
+    let sent = ((message_id.get() >> 22) + DISCORD_EPOCH_MILLIS) / 1000;
+
+    now.saturating_sub(sent) > BULK_DELETE_MAX_AGE_SECS
+}
+
+/// The smallest message id Discord could give to a message sent at `secs`,
+/// a Unix timestamp in seconds
+pub fn snowflake_at(secs: i64) -> Id<MessageMarker> {
+    let millis = u64::try_from(secs)
+        .unwrap_or(0)
+        .saturating_mul(1000)
+        .saturating_sub(DISCORD_EPOCH_MILLIS);
+
+    Id::new_checked(millis << 22).unwrap_or_else(|| Id::new(1))
+}
+
+/// Forum post and thread names can't be longer than this
+const THREAD_NAME_MAX_LENGTH: usize = 100;
+
+/// The name of a post or thread starting with `message`: its first line,
+/// shortened to fit
+pub fn default_thread_name(message: &Message) -> String {
+    let name = message
+        .content
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .map_or("moved messages", str::trim);
+
+    truncate_thread_name(name)
+}
+
+pub fn truncate_thread_name(name: &str) -> String {
+    if name.chars().count() <= THREAD_NAME_MAX_LENGTH {
+        return name.to_owned();
+    }
+
+    let mut truncated = name
+        .chars()
+        .take(THREAD_NAME_MAX_LENGTH - 1)
+        .collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+/// The name to upload a copy of `attachment` with, a spoiler is an
+/// attachment whose name starts with `SPOILER_`, whatever the case
+fn attachment_filename(attachment: &channel::Attachment) -> String {
+    let is_spoiler = attachment
+        .filename
+        .get(..SPOILER_PREFIX.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(SPOILER_PREFIX));
+
+    if is_spoiler {
+        format!("{SPOILER_PREFIX}{}", &attachment.filename[SPOILER_PREFIX.len()..])
+    } else {
+        attachment.filename.clone()
     }
 }
 
+/// Finds the tags of `forum` named in `names`, ignoring the case
+pub fn forum_tag_ids(forum: &Channel, names: &[String]) -> Result<Vec<Id<TagMarker>>> {
+    let available_tags = forum.available_tags.as_deref().unwrap_or_default();
+
+    names
+        .iter()
+        .map(|name| {
+            available_tags
+                .iter()
+                .find(|tag| tag.name.eq_ignore_ascii_case(name))
+                .map(|tag| tag.id)
+                .ok_or_else(|| UnknownForumTag(name.clone()).into())
+        })
+        .collect()
+}
+
 pub fn check(message: &Message) -> Result<()> {
     // if !message.attachments.is_empty() {
     //     return Err(CustomError::MessageAttachment.into());
@@ -0,0 +1,567 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sparkle_convenience::error::IntoError;
+use twilight_model::{
+    channel::{message::MessageType, Channel, Message},
+    gateway::payload::incoming::MessageUpdate,
+    id::{
+        marker::{ApplicationMarker, ChannelMarker, GuildMarker, MessageMarker, UserMarker, WebhookMarker},
+        Id,
+    },
+};
+
+use crate::{
+    job::{add_notes, KeepReactions},
+    message::{is_unknown_channel, MovedCopies, MovedCopy},
+    Context, CustomError,
+};
+
+/// Where the mirrors are saved when `MIRRORS_FILE` isn't set
+const DEFAULT_MIRRORS_FILE: &str = "mirrors/mirrors.json";
+/// How many copies are remembered in each target channel to forward edits
+/// and deletes to, the older messages are only mirrored once
+const MAX_REMEMBERED_COPIES: usize = 1000;
+
+/// A channel whose new messages are copied to another as they're sent
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Mirror {
+    pub guild_id: Id<GuildMarker>,
+    pub source_channel: Id<ChannelMarker>,
+    pub target_channel: Id<ChannelMarker>,
+    pub forward_edits: bool,
+    pub forward_deletes: bool,
+    /// The webhook the copies are sent with, to know them from the messages
+    /// to mirror even when another mirror's target is the source
+    #[serde(default)]
+    pub webhook_id: Option<Id<WebhookMarker>>,
+}
+
+/// The copies sent to a target channel, kept in memory only so the ones
+/// sent before a restart aren't edited or deleted anymore
+#[derive(Debug, Default)]
+struct TargetCopies {
+    copies: MovedCopies,
+    /// The notes added to the copies, which edits keep
+    notes: HashMap<Id<MessageMarker>, Vec<String>>,
+    /// Oldest first, to forget them in the order they were sent
+    order: VecDeque<Id<MessageMarker>>,
+}
+
+pub struct Mirrors {
+    /// Set with `ENABLE_MIRRORS`, mirroring needs the privileged message
+    /// content intent and the bot can't connect without it if it's not
+    /// enabled in the developer portal
+    pub enabled: bool,
+    path: PathBuf,
+    /// Saved to `path` whenever they change
+    saved: Mutex<Vec<Mirror>>,
+    copies: Mutex<HashMap<Id<ChannelMarker>, TargetCopies>>,
+    /// Held for a source channel while forwarding its messages so that the
+    /// copies are sent in order and an edit doesn't overtake the copy it
+    /// edits, the other channels are mirrored meanwhile
+    forwarding: Mutex<HashMap<Id<ChannelMarker>, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl Mirrors {
+    /// The mirrors saved in `MIRRORS_FILE`, none if it doesn't exist yet
+    pub fn from_env() -> Result<Self> {
+        let enabled = env::var("ENABLE_MIRRORS").is_ok_and(|enabled| enabled == "true");
+        let path = env::var("MIRRORS_FILE")
+            .map_or_else(|_| PathBuf::from(DEFAULT_MIRRORS_FILE), PathBuf::from);
+        let mirrors = if path.exists() {
+            serde_json::from_slice(&std::fs::read(&path)?)?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self::new(enabled, path, mirrors))
+    }
+
+    fn new(enabled: bool, path: PathBuf, mirrors: Vec<Mirror>) -> Self {
+        Self {
+            enabled,
+            path,
+            saved: Mutex::new(mirrors),
+            copies: Mutex::new(HashMap::new()),
+            forwarding: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The lock held while forwarding the messages of `source_channel`
+    fn forwarding(&self, source_channel: Id<ChannelMarker>) -> Arc<tokio::sync::Mutex<()>> {
+        Arc::clone(
+            self.forwarding
+                .lock()
+                .unwrap()
+                .entry(source_channel)
+                .or_default(),
+        )
+    }
+
+    pub fn in_guild(&self, guild_id: Id<GuildMarker>) -> Vec<Mirror> {
+        self.saved
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|mirror| mirror.guild_id == guild_id)
+            .copied()
+            .collect()
+    }
+
+    fn of_source(&self, channel_id: Id<ChannelMarker>) -> Vec<Mirror> {
+        self.saved
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|mirror| mirror.source_channel == channel_id)
+            .copied()
+            .collect()
+    }
+
+    /// Whether `webhook_id` sends the copies of a mirror
+    fn sent_by_mirror(&self, webhook_id: Id<WebhookMarker>) -> bool {
+        self.saved
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|mirror| mirror.webhook_id == Some(webhook_id))
+    }
+
+    /// Adds `mirror`, returning whether its channels weren't already mirrored
+    pub async fn add(&self, mirror: Mirror) -> Result<bool> {
+        let mirrors = {
+            let mut mirrors = self.saved.lock().unwrap();
+            if mirrors.iter().any(|other| {
+                other.source_channel == mirror.source_channel && other.target_channel == mirror.target_channel
+            }) {
+                return Ok(false);
+            }
+            mirrors.push(mirror);
+            mirrors.clone()
+        };
+        self.save(&mirrors).await?;
+
+        Ok(true)
+    }
+
+    /// Removes the mirrors of `source_channel`, only the one to
+    /// `target_channel` if it's set, returning the ones that were removed
+    pub async fn remove(
+        &self,
+        source_channel: Id<ChannelMarker>,
+        target_channel: Option<Id<ChannelMarker>>,
+    ) -> Result<Vec<Mirror>> {
+        let (removed, mirrors) = {
+            let mut mirrors = self.saved.lock().unwrap();
+            let (removed, kept) = mirrors.iter().partition::<Vec<_>, _>(|mirror| {
+                mirror.source_channel == source_channel
+                    && target_channel.is_none_or(|channel_id| mirror.target_channel == channel_id)
+            });
+            *mirrors = kept;
+            (removed, mirrors.clone())
+        };
+        if !removed.is_empty() {
+            self.save(&mirrors).await?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Remembers that the copies of `mirror` are sent with `webhook_id`, the
+    /// webhook is created again if it's deleted
+    async fn set_webhook_id(&self, mirror: Mirror, webhook_id: Id<WebhookMarker>) -> Result<()> {
+        let mirrors = {
+            let mut mirrors = self.saved.lock().unwrap();
+            for saved in mirrors.iter_mut().filter(|saved| {
+                saved.source_channel == mirror.source_channel && saved.target_channel == mirror.target_channel
+            }) {
+                saved.webhook_id = Some(webhook_id);
+            }
+            mirrors.clone()
+        };
+
+        self.save(&mirrors).await
+    }
+
+    async fn save(&self, mirrors: &[Mirror]) -> Result<()> {
+        // Write then rename so that a crash never leaves half the file behind
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_vec(mirrors)?).await?;
+        tokio::fs::rename(tmp_path, &self.path).await?;
+
+        Ok(())
+    }
+
+    fn copies_in(&self, target_channel: Id<ChannelMarker>) -> MovedCopies {
+        self.copies
+            .lock()
+            .unwrap()
+            .get(&target_channel)
+            .map(|target| target.copies.clone())
+            .unwrap_or_default()
+    }
+
+    /// The copy of `message_id` in `target_channel` with the notes added to
+    /// it
+    fn copy_of(
+        &self,
+        target_channel: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+    ) -> Option<(MovedCopy, Vec<String>)> {
+        self.copies
+            .lock()
+            .unwrap()
+            .get(&target_channel)
+            .and_then(|target| {
+                let copy = *target.copies.get(&message_id)?;
+                let notes = target.notes.get(&message_id).cloned().unwrap_or_default();
+                Some((copy, notes))
+            })
+    }
+
+    fn remember(
+        &self,
+        target_channel: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        copy: MovedCopy,
+        notes: Vec<String>,
+    ) {
+        let mut copies = self.copies.lock().unwrap();
+        let target = copies.entry(target_channel).or_default();
+        target.copies.insert(message_id, copy);
+        if !notes.is_empty() {
+            target.notes.insert(message_id, notes);
+        }
+        target.order.push_back(message_id);
+        if target.order.len() > MAX_REMEMBERED_COPIES {
+            if let Some(oldest) = target.order.pop_front() {
+                target.copies.remove(&oldest);
+                target.notes.remove(&oldest);
+            }
+        }
+        drop(copies);
+    }
+
+    fn forget(&self, target_channel: Id<ChannelMarker>, message_id: Id<MessageMarker>) -> Option<MovedCopy> {
+        let mut copies = self.copies.lock().unwrap();
+        let target = copies.get_mut(&target_channel)?;
+        target.order.retain(|id| *id != message_id);
+        target.notes.remove(&message_id);
+        let copy = target.copies.remove(&message_id);
+        drop(copies);
+
+        copy
+    }
+}
+
+impl Context {
+    /// Copies `message` to the channels its channel is mirrored to
+    pub async fn mirror_message(&self, message: &Message) {
+        let mirrors = self.mirrors.of_source(message.channel_id);
+        if mirrors.is_empty()
+            || self.is_own_copy(message)
+            || !matches!(message.kind, MessageType::Regular | MessageType::Reply)
+        {
+            return;
+        }
+
+        let forwarding = self.mirrors.forwarding(message.channel_id);
+        let _forwarding = forwarding.lock().await;
+        for mirror in mirrors {
+            if let Err(err) = Box::pin(self.forward_message(message, mirror)).await {
+                self.mirror_failed(mirror, &err).await;
+            }
+        }
+    }
+
+    /// Edits the copies of the message `update` is about, in the mirrors
+    /// that forward edits
+    pub async fn mirror_update(&self, update: &MessageUpdate) {
+        // Updates without an edit are Discord adding the embeds of links
+        let (Some(content), Some(_)) = (&update.content, update.edited_timestamp) else {
+            return;
+        };
+
+        let forwarding = self.mirrors.forwarding(update.channel_id);
+        let _forwarding = forwarding.lock().await;
+        for mirror in self.mirrors.of_source(update.channel_id) {
+            let Some((copy, notes)) = self
+                .mirrors
+                .copy_of(mirror.target_channel, update.id)
+                .filter(|_| mirror.forward_edits)
+            else {
+                continue;
+            };
+            let mut content = content.clone();
+            add_notes(&mut content, &notes);
+            if let Err(err) = self.update_copy(mirror, copy, &content).await {
+                self.mirror_failed(mirror, &err).await;
+            }
+        }
+    }
+
+    /// Deletes the copies of `message_ids`, in the mirrors that forward
+    /// deletes
+    pub async fn mirror_delete(&self, channel_id: Id<ChannelMarker>, message_ids: &[Id<MessageMarker>]) {
+        let forwarding = self.mirrors.forwarding(channel_id);
+        let _forwarding = forwarding.lock().await;
+        for mirror in self.mirrors.of_source(channel_id) {
+            if !mirror.forward_deletes {
+                continue;
+            }
+            for message_id in message_ids {
+                let Some(copy) = self.mirrors.forget(mirror.target_channel, *message_id) else {
+                    continue;
+                };
+                if let Err(err) = self.delete_copy(mirror, copy).await {
+                    self.mirror_failed(mirror, &err).await;
+                }
+            }
+        }
+    }
+
+    async fn forward_message(&self, message: &Message, mirror: Mirror) -> Result<()> {
+        let target = self.bot.http.channel(mirror.target_channel).await?.model().await?;
+        let moved = self.mirrors.copies_in(mirror.target_channel);
+        let copy = self
+            .copy_message(
+                message,
+                &target,
+                None,
+                &moved,
+                self.large_attachments,
+                KeepReactions::Drop,
+            )
+            .await?;
+        if let Some(first) = copy.messages.first() {
+            self.mirrors
+                .remember(mirror.target_channel, message.id, first.into(), copy.notes);
+            if let Some(webhook_id) = first.webhook_id.filter(|id| mirror.webhook_id != Some(*id)) {
+                self.mirrors.set_webhook_id(mirror, webhook_id).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Logs why `mirror` failed, stopping it if its target was deleted so
+    /// that it doesn't fail again with every message
+    async fn mirror_failed(&self, mirror: Mirror, err: &anyhow::Error) {
+        if !is_unknown_channel(err) {
+            self.bot
+                .log(format!(
+                    "couldn't mirror <#{}> to <#{}>: {err:?}",
+                    mirror.source_channel, mirror.target_channel
+                ))
+                .await;
+            return;
+        }
+
+        let message = match self
+            .mirrors
+            .remove(mirror.source_channel, Some(mirror.target_channel))
+            .await
+        {
+            Ok(_) => format!(
+                "stopped mirroring <#{}> to {}, it was deleted",
+                mirror.source_channel, mirror.target_channel
+            ),
+            Err(err) => format!(
+                "couldn't stop mirroring <#{}> to the deleted {}: {err:?}",
+                mirror.source_channel, mirror.target_channel
+            ),
+        };
+        self.bot.log(message).await;
+    }
+
+    /// Whether `message` was sent by the bot, so that mirroring channels to
+    /// each other doesn't copy the copies back
+    fn is_own_copy(&self, message: &Message) -> bool {
+        is_copy(message, self.bot.user.id, self.bot.application.id, |webhook_id| {
+            self.mirrors.sent_by_mirror(webhook_id)
+                || self
+                    .webhooks
+                    .lock()
+                    .unwrap()
+                    .values()
+                    .any(|webhook| webhook.id == webhook_id)
+        })
+    }
+
+    async fn update_copy(&self, mirror: Mirror, copy: MovedCopy, content: &str) -> Result<()> {
+        let target = self.bot.http.channel(mirror.target_channel).await?.model().await?;
+        let webhook = self.channel_webhook(webhook_channel(&target)?).await?;
+        let response = self
+            .pacer
+            .run(|| async {
+                let mut update = self
+                    .bot
+                    .http
+                    .update_webhook_message(webhook.id, &webhook.token, copy.message_id)
+                    .content(Some(content))
+                    .map_err(|_| CustomError::MessageTooLong)?;
+                if target.kind.is_thread() {
+                    update = update.thread_id(copy.channel_id);
+                }
+                Ok(update.await?)
+            })
+            .await?;
+        self.pacer.observe(&response).await;
+
+        Ok(())
+    }
+
+    async fn delete_copy(&self, mirror: Mirror, copy: MovedCopy) -> Result<()> {
+        let target = self.bot.http.channel(mirror.target_channel).await?.model().await?;
+        let webhook = self.channel_webhook(webhook_channel(&target)?).await?;
+        let response = self
+            .pacer
+            .run(|| async {
+                let mut delete = self
+                    .bot
+                    .http
+                    .delete_webhook_message(webhook.id, &webhook.token, copy.message_id);
+                if target.kind.is_thread() {
+                    delete = delete.thread_id(copy.channel_id);
+                }
+                Ok(delete.await?)
+            })
+            .await?;
+        self.pacer.observe(&response).await;
+
+        Ok(())
+    }
+}
+
+/// Whether `message` was sent by the bot with the ID `user_id`, its
+/// application or one of its webhooks, which `own_webhook` tells apart
+fn is_copy(
+    message: &Message,
+    user_id: Id<UserMarker>,
+    application_id: Id<ApplicationMarker>,
+    own_webhook: impl Fn(Id<WebhookMarker>) -> bool,
+) -> bool {
+    message.author.id == user_id
+        || message.application_id == Some(application_id)
+        || message.webhook_id.is_some_and(own_webhook)
+}
+
+/// The channel the webhook sending to `target` belongs to, threads use their
+/// parent's
+pub fn webhook_channel(target: &Channel) -> Result<Id<ChannelMarker>> {
+    if target.kind.is_thread() {
+        Ok(target.parent_id.ok()?)
+    } else {
+        Ok(target.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::json;
+    use twilight_model::{
+        channel::Message,
+        id::{
+            marker::{ChannelMarker, UserMarker, WebhookMarker},
+            Id,
+        },
+    };
+
+    use super::{is_copy, Mirror, Mirrors};
+
+    const BOT_ID: u64 = 10;
+    const APPLICATION_ID: u64 = 11;
+
+    fn mirror(
+        source_channel: Id<ChannelMarker>,
+        target_channel: Id<ChannelMarker>,
+        webhook_id: Id<WebhookMarker>,
+    ) -> Mirror {
+        Mirror {
+            guild_id: Id::new(1),
+            source_channel,
+            target_channel,
+            forward_edits: false,
+            forward_deletes: false,
+            webhook_id: Some(webhook_id),
+        }
+    }
+
+    /// A message sent in `channel_id`, by a webhook if `webhook_id` is set
+    fn message(
+        channel_id: Id<ChannelMarker>,
+        author_id: Id<UserMarker>,
+        webhook_id: Option<Id<WebhookMarker>>,
+    ) -> Message {
+        serde_json::from_value(json!({
+            "id": "20",
+            "channel_id": channel_id,
+            "author": {
+                "id": author_id,
+                "username": "someone",
+                "discriminator": "0000",
+                "avatar": null,
+            },
+            "webhook_id": webhook_id,
+            "content": "hi",
+            "timestamp": "2024-01-01T00:00:00+00:00",
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+            "pinned": false,
+            "type": 0,
+        }))
+        .unwrap()
+    }
+
+    fn is_mirrored_copy(mirrors: &Mirrors, message: &Message) -> bool {
+        is_copy(message, Id::new(BOT_ID), Id::new(APPLICATION_ID), |webhook_id| {
+            mirrors.sent_by_mirror(webhook_id)
+        })
+    }
+
+    #[test]
+    fn mirror_pair_does_not_copy_copies_back() {
+        let (channel_a, channel_b) = (Id::new(2), Id::new(3));
+        let (webhook_a, webhook_b) = (Id::new(4), Id::new(5));
+        let mirrors = Mirrors::new(
+            true,
+            PathBuf::new(),
+            vec![
+                mirror(channel_a, channel_b, webhook_b),
+                mirror(channel_b, channel_a, webhook_a),
+            ],
+        );
+
+        // A message sent in A is copied to B with B's webhook, the copy is
+        // in a mirrored channel but must not be copied back to A, and the
+        // other way around. Webhooks post with their own ID as the author.
+        let copy_in_b = message(channel_b, webhook_b.cast(), Some(webhook_b));
+        assert_eq!(mirrors.of_source(channel_b)[0].target_channel, channel_a);
+        assert!(is_mirrored_copy(&mirrors, &copy_in_b));
+        let copy_in_a = message(channel_a, webhook_a.cast(), Some(webhook_a));
+        assert!(is_mirrored_copy(&mirrors, &copy_in_a));
+
+        // Members and other webhooks are mirrored
+        assert!(!is_mirrored_copy(&mirrors, &message(channel_a, Id::new(6), None)));
+        assert!(!is_mirrored_copy(&mirrors, &message(channel_a, Id::new(7), Some(Id::new(7)))));
+        // And the bot's own messages aren't
+        assert!(is_mirrored_copy(&mirrors, &message(channel_a, Id::new(BOT_ID), None)));
+    }
+}
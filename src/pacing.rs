@@ -0,0 +1,105 @@
+use std::{env, future::Future, time::Duration};
+
+use anyhow::Result;
+use tokio::sync::Semaphore;
+use twilight_http::{
+    api_error::{ApiError, RatelimitedApiError},
+    error::ErrorType,
+    Response,
+};
+
+/// How many requests of moves and deletions can run at once when
+/// `MAX_CONCURRENT_REQUESTS` isn't set
+const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// How many times a rate limited request is sent again before giving up
+const MAX_RETRIES: u32 = 5;
+
+/// The first wait after a rate limit, doubled with every retry
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Paces the requests of moves and deletions with the rate limit headers
+/// Discord sends back instead of fixed sleeps
+pub struct Pacer {
+    permits: Semaphore,
+}
+
+impl Pacer {
+    pub fn from_env() -> Self {
+        let max_concurrent_requests = env::var("MAX_CONCURRENT_REQUESTS")
+            .ok()
+            .and_then(|max| max.parse().ok())
+            .filter(|max| *max > 0)
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_REQUESTS);
+
+        Self {
+            permits: Semaphore::new(max_concurrent_requests),
+        }
+    }
+
+    /// Runs `request` once there's room for it, running it again with a
+    /// backoff when it's rate limited
+    ///
+    /// `request` must not call `run` itself, it would wait for a permit it
+    /// holds.
+    pub async fn run<T, F, Fut>(&self, mut request: F) -> Result<T>
+    where
+        F: FnMut() -> Fut + Send,
+        Fut: Future<Output = Result<T>> + Send,
+    {
+        let _permit = self.permits.acquire().await?;
+
+        let mut retries = 0;
+        loop {
+            match request().await {
+                Err(err) if retries < MAX_RETRIES => {
+                    let Some(retry_after) = err.downcast_ref().and_then(retry_after) else {
+                        return Err(err);
+                    };
+                    let backoff = BASE_BACKOFF * 2_u32.pow(retries);
+                    retries += 1;
+                    tokio::time::sleep(retry_after.max(backoff)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Waits for the bucket of `response` to reset if it's used up, so that
+    /// the next request doesn't hit the rate limit
+    pub async fn observe<T: Sync>(&self, response: &Response<T>) {
+        let mut remaining = None;
+        let mut reset_after = None;
+        for (name, value) in response.headers() {
+            let value = std::str::from_utf8(value).ok();
+            match name {
+                "x-ratelimit-remaining" => {
+                    remaining = value.and_then(|value| value.parse::<u64>().ok());
+                }
+                "x-ratelimit-reset-after" => {
+                    reset_after = value.and_then(|value| value.parse::<f64>().ok());
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(0), Some(reset_after)) = (remaining, reset_after) {
+            tokio::time::sleep(Duration::from_secs_f64(reset_after.max(0.0))).await;
+        }
+    }
+}
+
+pub fn is_rate_limited(err: &twilight_http::Error) -> bool {
+    retry_after(err).is_some()
+}
+
+fn retry_after(err: &twilight_http::Error) -> Option<Duration> {
+    match err.kind() {
+        ErrorType::Response {
+            error: ApiError::Ratelimited(RatelimitedApiError { retry_after, .. }),
+            ..
+        } => Some(Duration::from_secs_f64(retry_after.max(0.0))),
+        ErrorType::Response { status, .. } if status.get() == 429 => Some(BASE_BACKOFF),
+        _ => None,
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::Result;
+use sparkle_convenience::{error::IntoError, prettify::Prettify};
+use twilight_http::{
+    api_error::{ApiError, GeneralApiError},
+    error::ErrorType,
+};
+use twilight_model::{
+    channel::{permission_overwrite::PermissionOverwrite, Channel},
+    guild::{Guild, Permissions},
+    id::{
+        marker::{GuildMarker, RoleMarker, UserMarker},
+        Id,
+    },
+};
+use twilight_util::permission_calculator::PermissionCalculator;
+
+use crate::{Context, CustomError};
+
+/// What the bot needs in the channel the messages are copied to, other than
+/// sending messages, since they're sent through its webhook
+const BOT_TARGET_PERMISSIONS: Permissions = Permissions::VIEW_CHANNEL.union(Permissions::MANAGE_WEBHOOKS);
+/// What the user needs in the channel the messages are moved from
+const SOURCE_PERMISSIONS: Permissions = Permissions::VIEW_CHANNEL.union(Permissions::READ_MESSAGE_HISTORY);
+/// The error Discord returns when the user isn't in the guild
+const UNKNOWN_MEMBER_CODE: u64 = 10007;
+
+/// The roles of a member of a guild, to know what they can do in its
+/// channels
+#[derive(Debug)]
+pub struct MemberPermissions {
+    guild_id: Id<GuildMarker>,
+    owner_id: Id<UserMarker>,
+    user_id: Id<UserMarker>,
+    everyone: Permissions,
+    roles: Vec<(Id<RoleMarker>, Permissions)>,
+}
+
+impl MemberPermissions {
+    /// What the member can do in the guild, regardless of the channel
+    pub fn root(&self) -> Permissions {
+        self.calculator().root()
+    }
+
+    /// What the member can do in `channel`, with the `overwrites` that apply
+    /// to it
+    pub fn in_channel(&self, channel: &Channel, overwrites: &[PermissionOverwrite]) -> Permissions {
+        self.calculator().in_channel(channel.kind, overwrites)
+    }
+
+    fn calculator(&self) -> PermissionCalculator<'_> {
+        PermissionCalculator::new(self.guild_id, self.user_id, self.everyone, &self.roles)
+            .owner_id(self.owner_id)
+    }
+}
+
+impl Context {
+    /// The permissions of `user_id` in `guild`, which fails if they aren't in
+    /// it
+    pub async fn member_permissions(&self, guild: &Guild, user_id: Id<UserMarker>) -> Result<MemberPermissions> {
+        let member = match self.bot.http.guild_member(guild.id, user_id).await {
+            Ok(response) => response.model().await?,
+            Err(err) if is_unknown_member(&err) => return Err(CustomError::NotInTargetGuild.into()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let everyone = guild
+            .roles
+            .iter()
+            .find_map(|role| (role.id.cast() == guild.id).then_some(role.permissions))
+            .ok()?;
+        let roles = guild
+            .roles
+            .iter()
+            .filter(|role| member.roles.contains(&role.id))
+            .map(|role| (role.id, role.permissions))
+            .collect();
+
+        Ok(MemberPermissions {
+            guild_id: guild.id,
+            owner_id: guild.owner_id,
+            user_id,
+            everyone,
+            roles,
+        })
+    }
+
+    /// The overwrites that apply to `channel`, threads have their parent's
+    pub async fn permission_overwrites(&self, channel: &Channel) -> Result<Vec<PermissionOverwrite>> {
+        if channel.kind.is_thread() {
+            Ok(self
+                .bot
+                .http
+                .channel(channel.parent_id.ok()?)
+                .await?
+                .model()
+                .await?
+                .permission_overwrites
+                .ok()?)
+        } else {
+            Ok(channel.permission_overwrites.clone().ok()?)
+        }
+    }
+
+    /// Checks that both `user_id` and the bot can send messages in
+    /// `channel`, which can be in another guild than the messages
+    pub async fn check_target(&self, channel: &Channel, user_id: Id<UserMarker>) -> Result<()> {
+        let guild = self.bot.http.guild(channel.guild_id.ok()?).await?.model().await?;
+        let overwrites = self.permission_overwrites(channel).await?;
+        let user = self.member_permissions(&guild, user_id).await?;
+        let bot = self.member_permissions(&guild, self.bot.user.id).await?;
+
+        check_target(&user, &bot, channel, &overwrites)
+    }
+
+    /// Checks that `user_id` can read the messages of `channel`, and delete
+    /// them when `delete` is set
+    pub async fn check_source(&self, channel: &Channel, user_id: Id<UserMarker>, delete: bool) -> Result<()> {
+        let guild = self.bot.http.guild(channel.guild_id.ok()?).await?.model().await?;
+        let overwrites = self.permission_overwrites(channel).await?;
+        let permissions = self
+            .member_permissions(&guild, user_id)
+            .await?
+            .in_channel(channel, &overwrites);
+
+        if !permissions.contains(SOURCE_PERMISSIONS) {
+            return Err(CustomError::ReadMessagesPermissionMissing.into());
+        }
+        if delete && !permissions.contains(Permissions::MANAGE_MESSAGES) {
+            return Err(CustomError::ManageMessagesPermissionsMissing.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that both `user` and `bot` can send messages in `channel`
+pub fn check_target(
+    user: &MemberPermissions,
+    bot: &MemberPermissions,
+    channel: &Channel,
+    overwrites: &[PermissionOverwrite],
+) -> Result<()> {
+    let send_permission = if channel.kind.is_thread() {
+        Permissions::SEND_MESSAGES_IN_THREADS
+    } else {
+        Permissions::SEND_MESSAGES
+    };
+
+    if !user.in_channel(channel, overwrites).contains(send_permission) {
+        return Err(CustomError::SendMessagesPermissionMissing.into());
+    }
+    let missing = (BOT_TARGET_PERMISSIONS | send_permission) - bot.in_channel(channel, overwrites);
+    if !missing.is_empty() {
+        return Err(CustomError::BotPermissionsMissing(missing.prettify()).into());
+    }
+
+    Ok(())
+}
+
+const fn is_unknown_member(err: &twilight_http::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorType::Response {
+            error: ApiError::General(GeneralApiError { code, .. }),
+            ..
+        } if *code == UNKNOWN_MEMBER_CODE
+    )
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use twilight_http::{
+    api_error::{ApiError, GeneralApiError},
+    error::ErrorType,
+};
+use twilight_model::id::{
+    marker::{ChannelMarker, MessageMarker},
+    Id,
+};
+
+use crate::{job::MoveJob, message::MovedCopy, Context};
+
+/// How many messages a channel can have pinned
+const MAX_PINS: usize = 50;
+/// The error Discord returns when a channel already has `MAX_PINS` pins
+const MAX_PINS_CODE: u64 = 30003;
+
+/// A copy of a pinned message that couldn't be pinned
+#[derive(Clone, Debug)]
+pub struct UnpinnedCopy {
+    pub copy: MovedCopy,
+    pub reason: String,
+}
+
+impl Context {
+    /// The messages pinned in `channel_id`
+    pub async fn pinned_ids(&self, channel_id: Id<ChannelMarker>) -> Result<Vec<Id<MessageMarker>>> {
+        Ok(self
+            .bot
+            .http
+            .pins(channel_id)
+            .await?
+            .model()
+            .await?
+            .into_iter()
+            .map(|message| message.id)
+            .collect())
+    }
+
+    /// Pins the copies of the messages of `job` that were pinned, oldest
+    /// first until the channel they're in is full, returning the ones that
+    /// couldn't be pinned
+    pub async fn restore_pins(&self, job: &MoveJob) -> Vec<UnpinnedCopy> {
+        let mut pin_counts: HashMap<Id<ChannelMarker>, usize> = HashMap::new();
+        let mut unpinned = Vec::new();
+
+        let copies = job
+            .message_ids
+            .iter()
+            .filter(|message_id| job.pinned.contains(message_id))
+            .filter_map(|message_id| job.copies.get(message_id));
+        for copy in copies {
+            let count = match pin_counts.get(&copy.channel_id) {
+                Some(count) => *count,
+                None => match self.pinned_ids(copy.channel_id).await {
+                    Ok(pinned) => pinned.len(),
+                    Err(err) => {
+                        unpinned.push(UnpinnedCopy {
+                            copy: *copy,
+                            reason: err.to_string(),
+                        });
+                        continue;
+                    }
+                },
+            };
+            if count >= MAX_PINS {
+                unpinned.push(UnpinnedCopy {
+                    copy: *copy,
+                    reason: format!("<#{}> already has {MAX_PINS} pins", copy.channel_id),
+                });
+                pin_counts.insert(copy.channel_id, count);
+                continue;
+            }
+
+            match self.bot.http.create_pin(copy.channel_id, copy.message_id).await {
+                Ok(_) => {
+                    pin_counts.insert(copy.channel_id, count + 1);
+                }
+                Err(err) => {
+                    let reason = if is_max_pins(&err) {
+                        pin_counts.insert(copy.channel_id, MAX_PINS);
+                        format!("<#{}> already has {MAX_PINS} pins", copy.channel_id)
+                    } else {
+                        err.to_string()
+                    };
+                    unpinned.push(UnpinnedCopy {
+                        copy: *copy,
+                        reason,
+                    });
+                }
+            }
+        }
+
+        unpinned
+    }
+}
+
+const fn is_max_pins(err: &twilight_http::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorType::Response {
+            error: ApiError::General(GeneralApiError { code, .. }),
+            ..
+        } if *code == MAX_PINS_CODE
+    )
+}
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use twilight_http::request::channel::reaction::RequestReactionType;
+use twilight_model::channel::message::ReactionType;
+use twilight_model::channel::Message;
+
+use crate::Context;
+
+/// The longest content a message can have
+const MESSAGE_MAX_LENGTH: usize = 2000;
+/// Users listed by name for each reaction in a summary, the rest are only
+/// counted
+const MAX_LISTED_REACTORS: u16 = 10;
+
+impl Context {
+    /// `message` with a line summing up its reactions, with the first users
+    /// who reacted when `with_users` is set. The users are left out when the
+    /// content would be too long and the whole summary if it still is.
+    pub async fn with_reactions_summary(&self, message: &Message, with_users: bool) -> Result<Message> {
+        let mut message = message.clone();
+        if message.reactions.is_empty() {
+            return Ok(message);
+        }
+
+        let mut summary = summary_line(&message, &[]);
+        if with_users {
+            let mut reactors = Vec::new();
+            for reaction in &message.reactions {
+                reactors.push(self.reactor_names(&message, &reaction.emoji).await?);
+            }
+            let with_reactors = summary_line(&message, &reactors);
+            if fits(&message.content, &with_reactors) {
+                summary = with_reactors;
+            }
+        }
+        if !fits(&message.content, &summary) {
+            return Ok(message);
+        }
+
+        if !message.content.is_empty() {
+            message.content.push('\n');
+        }
+        message.content.push_str(&summary);
+
+        Ok(message)
+    }
+
+    /// Reacts to `copy` with the reactions of `message`, the emojis the bot
+    /// can't use are left out
+    pub async fn add_reactions(&self, message: &Message, copy: &Message) {
+        for reaction in &message.reactions {
+            let Some(emoji) = request_emoji(&reaction.emoji) else {
+                continue;
+            };
+            let result = self
+                .pacer
+                .run(|| async {
+                    Ok(self
+                        .bot
+                        .http
+                        .create_reaction(copy.channel_id, copy.id, &emoji)
+                        .await?)
+                })
+                .await;
+            match result {
+                Ok(response) => self.pacer.observe(&response).await,
+                Err(err) => {
+                    self.bot
+                        .log(format!(
+                            "couldn't react to {} with {}: {err:?}",
+                            copy.id,
+                            display(&reaction.emoji)
+                        ))
+                        .await;
+                }
+            }
+        }
+    }
+
+    /// The names of the first users who reacted to `message` with `emoji`
+    async fn reactor_names(&self, message: &Message, emoji: &ReactionType) -> Result<Vec<String>> {
+        let Some(emoji) = request_emoji(emoji) else {
+            return Ok(Vec::new());
+        };
+        let users = self
+            .bot
+            .http
+            .reactions(message.channel_id, message.id, &emoji)
+            .limit(MAX_LISTED_REACTORS)?
+            .await?
+            .models()
+            .await?;
+
+        Ok(users
+            .into_iter()
+            .map(|user| user.global_name.unwrap_or(user.name))
+            .collect())
+    }
+}
+
+/// A line like `*reactions: 👍 3 (alice, bob, +1), ❤️ 1*`, `reactors` are
+/// the names listed for each reaction if any
+fn summary_line(message: &Message, reactors: &[Vec<String>]) -> String {
+    let reactions = message
+        .reactions
+        .iter()
+        .enumerate()
+        .map(|(idx, reaction)| {
+            let names = reactors.get(idx).filter(|names| !names.is_empty());
+            let Some(names) = names else {
+                return format!("{} {}", display(&reaction.emoji), reaction.count);
+            };
+            let others = reaction.count.saturating_sub(names.len() as u64);
+            let others = if others == 0 {
+                String::new()
+            } else {
+                format!(", +{others}")
+            };
+            format!(
+                "{} {} ({}{others})",
+                display(&reaction.emoji),
+                reaction.count,
+                names.join(", ")
+            )
+        })
+        .collect::<Vec<_>>();
+
+    format!("*reactions: {}*", reactions.join(", "))
+}
+
+fn fits(content: &str, summary: &str) -> bool {
+    content.chars().count() + summary.chars().count() < MESSAGE_MAX_LENGTH
+}
+
+pub fn display(emoji: &ReactionType) -> String {
+    match emoji {
+        ReactionType::Custom {
+            animated,
+            id,
+            name: Some(name),
+        } => format!("<{}:{name}:{id}>", if *animated { "a" } else { "" }),
+        ReactionType::Custom { name: None, .. } => "(deleted emoji)".to_owned(),
+        ReactionType::Unicode { name } => name.clone(),
+    }
+}
+
+/// The emoji to react with, the ones that were deleted can't be used
+fn request_emoji(emoji: &ReactionType) -> Option<RequestReactionType<'_>> {
+    match emoji {
+        ReactionType::Custom { id, name, .. } => name.as_ref().map(|name| RequestReactionType::Custom {
+            id: *id,
+            name: Some(name),
+        }),
+        ReactionType::Unicode { name } => Some(RequestReactionType::Unicode { name }),
+    }
+}
@@ -0,0 +1,648 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use regex::{Captures, Regex};
+use twilight_model::channel::message::{Embed, ReactionType};
+use twilight_model::channel::{Attachment, Channel, Message};
+use twilight_model::util::Timestamp;
+
+use crate::attachments::format_bytes;
+use crate::message::{avatar_url, display_name};
+use crate::Context;
+
+/// Messages of the same author sent closer than this are shown under the
+/// same header, like in Discord
+const GROUP_MAX_GAP_SECS: i64 = 7 * 60;
+/// How much of the replied message is quoted
+const REPLY_PREVIEW_LENGTH: usize = 100;
+
+const STYLE: &str = r#"
+body { margin: 0; background: #313338; color: #dbdee1; font: 16px/1.375 "gg sans", "Noto Sans", "Helvetica Neue", Helvetica, Arial, sans-serif; }
+a { color: #00a8fc; text-decoration: none; }
+a:hover { text-decoration: underline; }
+header { padding: 16px; border-bottom: 1px solid #1f2023; }
+header h1 { margin: 0; font-size: 20px; color: #f2f3f5; }
+header p { margin: 4px 0 0; color: #949ba4; font-size: 14px; }
+.message { display: flex; padding: 2px 16px; }
+.message.first { margin-top: 16px; }
+.message:hover { background: #2e3035; }
+.gutter { flex: 0 0 56px; }
+.avatar { width: 40px; height: 40px; border-radius: 50%; }
+.body { min-width: 0; flex: 1; }
+.author { font-weight: 600; color: #f2f3f5; }
+.bot { margin-left: 4px; padding: 0 4px; border-radius: 3px; background: #5865f2; color: #fff; font-size: 10px; vertical-align: middle; }
+.time, .edited { margin-left: 6px; color: #949ba4; font-size: 12px; }
+.content { white-space: pre-wrap; overflow-wrap: anywhere; }
+.content h1, .content h2, .content h3 { margin: 8px 0 0; color: #f2f3f5; }
+.content blockquote { margin: 0; padding-left: 12px; border-left: 4px solid #4e5058; }
+.content pre { margin: 4px 0; padding: 8px; border-radius: 4px; background: #2b2d31; border: 1px solid #1e1f22; white-space: pre-wrap; }
+.content code { padding: 0 2px; border-radius: 3px; background: #2b2d31; font-size: 14px; }
+.content pre code { padding: 0; background: none; }
+.subtext { color: #949ba4; font-size: 12px; }
+.spoiler { background: #1e1f22; color: transparent; border-radius: 3px; cursor: pointer; }
+.spoiler:hover, .spoiler:active { color: inherit; }
+.mention { padding: 0 2px; border-radius: 3px; background: rgba(88, 101, 242, 0.3); color: #c9cdfb; }
+.emoji { width: 22px; height: 22px; vertical-align: bottom; }
+.reply { display: flex; align-items: center; gap: 4px; color: #b5bac1; font-size: 14px; white-space: nowrap; overflow: hidden; }
+.reply img { width: 16px; height: 16px; border-radius: 50%; }
+.reply .author { color: #b5bac1; }
+.attachment { display: block; margin-top: 4px; }
+.attachment img { max-width: 400px; max-height: 300px; border-radius: 8px; }
+.file { display: inline-block; padding: 10px; border-radius: 8px; background: #2b2d31; border: 1px solid #1e1f22; }
+.file span { margin-left: 8px; color: #949ba4; font-size: 12px; }
+.embed { max-width: 520px; margin-top: 4px; padding: 8px 16px 16px 12px; border-left: 4px solid; border-radius: 4px; background: #2b2d31; }
+.embed .embed-author, .embed .embed-title { margin-top: 8px; font-weight: 600; color: #f2f3f5; }
+.embed .embed-author { font-size: 14px; }
+.embed .embed-description { margin-top: 8px; font-size: 14px; }
+.embed .embed-description, .embed .field { white-space: pre-wrap; }
+.embed .fields { display: flex; flex-wrap: wrap; gap: 8px; margin-top: 8px; font-size: 14px; }
+.embed .field { flex: 1 1 100%; }
+.embed .field.inline { flex: 1 1 30%; }
+.embed .field-name { font-weight: 600; color: #f2f3f5; }
+.embed .embed-image { max-width: 100%; margin-top: 16px; border-radius: 4px; }
+.embed .embed-thumbnail { float: right; max-width: 80px; max-height: 80px; margin-left: 16px; border-radius: 4px; }
+.embed .embed-footer { margin-top: 8px; color: #949ba4; font-size: 12px; }
+.sticker { color: #949ba4; font-style: italic; }
+.reactions { display: flex; flex-wrap: wrap; gap: 4px; margin-top: 4px; }
+.reaction { padding: 2px 6px; border-radius: 8px; background: #2b2d31; border: 1px solid #1e1f22; font-size: 14px; }
+"#;
+
+/// The names mentions are shown with
+#[derive(Debug, Default)]
+struct Names {
+    users: HashMap<u64, String>,
+    roles: HashMap<u64, String>,
+    channels: HashMap<u64, String>,
+}
+
+impl Context {
+    /// A single HTML page showing `messages` of `channel` like Discord does,
+    /// with the images and avatars in it when `inline_images` is set so that
+    /// it still shows them once the messages are deleted
+    pub async fn transcript(&self, channel: &Channel, messages: &[Message], inline_images: bool) -> Result<String> {
+        let mut names = Names::default();
+        for message in messages {
+            names.users.insert(message.author.id.get(), display_name(message));
+            for mention in &message.mentions {
+                let name = mention
+                    .member
+                    .as_ref()
+                    .and_then(|member| member.nick.clone())
+                    .unwrap_or_else(|| mention.name.clone());
+                names.users.entry(mention.id.get()).or_insert(name);
+            }
+        }
+        if let Some(guild_id) = channel.guild_id {
+            for role in self.bot.http.roles(guild_id).await?.models().await? {
+                names.roles.insert(role.id.get(), role.name);
+            }
+            for guild_channel in self.bot.http.guild_channels(guild_id).await?.models().await? {
+                if let Some(name) = guild_channel.name {
+                    names.channels.insert(guild_channel.id.get(), name);
+                }
+            }
+        }
+
+        let mut images = HashMap::new();
+        if inline_images {
+            for message in messages {
+                if let Entry::Vacant(entry) = images.entry(avatar(message)) {
+                    // The avatar was changed since, the link to it is kept
+                    if let Ok(uri) = data_uri(entry.key(), "image/png").await {
+                        entry.insert(uri);
+                    }
+                }
+                for attachment in image_attachments(message) {
+                    let content_type = attachment.content_type.as_deref().unwrap_or("image/png");
+                    // Linked like the avatars if it can't be downloaded
+                    if let Ok(uri) = data_uri(&attachment.url, content_type).await {
+                        images.insert(attachment.url.clone(), uri);
+                    }
+                }
+            }
+        }
+
+        Ok(Transcript::new(names, images).render(channel, messages))
+    }
+}
+
+/// How much the images of `messages` add to the transcript when they're
+/// inlined, base64 makes them a third larger
+pub fn images_size(messages: &[Message]) -> u64 {
+    messages
+        .iter()
+        .flat_map(image_attachments)
+        .map(|attachment| attachment.size.div_ceil(3) * 4)
+        .sum()
+}
+
+fn image_attachments(message: &Message) -> impl Iterator<Item = &Attachment> {
+    message.attachments.iter().filter(|attachment| is_image(attachment))
+}
+
+fn is_image(attachment: &Attachment) -> bool {
+    attachment
+        .content_type
+        .as_deref()
+        .is_some_and(|content_type| content_type.starts_with("image/"))
+}
+
+async fn data_uri(url: &str, content_type: &str) -> Result<String> {
+    let bytes = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+    Ok(format!("data:{content_type};base64,{}", STANDARD.encode(bytes)))
+}
+
+/// The avatar of the author of `message`, the default one Discord picks if
+/// they have none
+fn avatar(message: &Message) -> String {
+    avatar_url(message).unwrap_or_else(|| {
+        format!(
+            "https://cdn.discordapp.com/embed/avatars/{}.png",
+            (message.author.id.get() >> 22) % 6
+        )
+    })
+}
+
+struct Transcript {
+    names: Names,
+    /// The data URIs of the inlined images by their URL
+    images: HashMap<String, String>,
+    code_block: Regex,
+    /// What's rendered on its own rather than formatted with the rest of the
+    /// line: inline code, links, custom emojis, mentions and timestamps
+    token: Regex,
+    placeholder: Regex,
+    formatting: Vec<(Regex, &'static str)>,
+}
+
+impl Transcript {
+    fn new(names: Names, images: HashMap<String, String>) -> Self {
+        Self {
+            names,
+            images,
+            code_block: Regex::new(r"(?s)```(?:[\w+-]*\n)?(.*?)```").unwrap(),
+            token: Regex::new(concat!(
+                r"`(?<code>[^`]+)`",
+                r"|\[(?<text>[^\]]+)\]\(<?(?<href>https?://[^\s)>]+)>?\)",
+                r"|<?(?<url>https?://[^\s<>]*[^\s<>.,:;'\x22)\]])>?",
+                r"|<(?<animated>a?):(?<emoji_name>\w+):(?<emoji>\d+)>",
+                r"|<@!?(?<user>\d+)>",
+                r"|<@&(?<role>\d+)>",
+                r"|<#(?<channel>\d+)>",
+                r"|<t:(?<time>-?\d+)(?::[tTdDfFR])?>",
+            ))
+            .unwrap(),
+            placeholder: Regex::new(r"\x00(\d+)\x00").unwrap(),
+            formatting: vec![
+                (Regex::new(r"\*\*(.+?)\*\*").unwrap(), "<strong>${1}</strong>"),
+                (Regex::new(r"__(.+?)__").unwrap(), "<u>${1}</u>"),
+                (Regex::new(r"\*(.+?)\*").unwrap(), "<em>${1}</em>"),
+                (Regex::new(r"\b_(.+?)_\b").unwrap(), "<em>${1}</em>"),
+                (Regex::new(r"~~(.+?)~~").unwrap(), "<s>${1}</s>"),
+                (Regex::new(r"\|\|(.+?)\|\|").unwrap(), r#"<span class="spoiler">${1}</span>"#),
+            ],
+        }
+    }
+
+    fn render(&self, channel: &Channel, messages: &[Message]) -> String {
+        let title = channel.name.as_deref().map_or_else(|| channel.id.to_string(), escape);
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>#{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<header><h1>#{title}</h1><p>{} messages</p></header>\n",
+            messages.len()
+        );
+
+        let mut previous: Option<&Message> = None;
+        for message in messages {
+            let continued = previous.is_some_and(|previous| {
+                previous.author.id == message.author.id
+                    && message.timestamp.as_secs() - previous.timestamp.as_secs() < GROUP_MAX_GAP_SECS
+                    && message.reference.is_none()
+            });
+            self.render_message(&mut html, message, continued);
+            previous = Some(message);
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    fn render_message(&self, html: &mut String, message: &Message, continued: bool) {
+        let _ = write!(
+            html,
+            "<div class=\"message{}\" id=\"m{}\">\n<div class=\"gutter\">",
+            if continued { "" } else { " first" },
+            message.id
+        );
+        if !continued {
+            let _ = write!(html, "<img class=\"avatar\" src=\"{}\" alt=\"\">", self.source(&avatar(message)));
+        }
+        html.push_str("</div>\n<div class=\"body\">\n");
+
+        if message.reference.is_some() {
+            html.push_str(&self.reply(message));
+        }
+        if !continued {
+            let _ = writeln!(
+                html,
+                "<div><span class=\"author\" title=\"{}\">{}</span>{}<span class=\"time\">{}</span></div>",
+                escape(&message.author.name),
+                escape(&display_name(message)),
+                if message.author.bot { "<span class=\"bot\">BOT</span>" } else { "" },
+                format_time(message.timestamp)
+            );
+        }
+
+        let _ = write!(html, "<div class=\"content\">{}", self.markdown(&message.content));
+        if let Some(edited) = message.edited_timestamp {
+            let _ = write!(html, "<span class=\"edited\" title=\"{}\">(edited)</span>", format_time(edited));
+        }
+        html.push_str("</div>\n");
+
+        for attachment in &message.attachments {
+            if is_image(attachment) {
+                let _ = writeln!(
+                    html,
+                    "<a class=\"attachment\" href=\"{}\"><img src=\"{}\" alt=\"{}\"></a>",
+                    escape(&attachment.url),
+                    self.source(&attachment.url),
+                    escape(attachment.description.as_deref().unwrap_or(&attachment.filename))
+                );
+            } else {
+                let _ = writeln!(
+                    html,
+                    "<div class=\"attachment\"><a class=\"file\" href=\"{}\">{}<span>{}</span></a></div>",
+                    escape(&attachment.url),
+                    escape(&attachment.filename),
+                    format_bytes(attachment.size)
+                );
+            }
+        }
+        for embed in &message.embeds {
+            html.push_str(&self.embed(embed));
+        }
+        for sticker in &message.sticker_items {
+            let _ = writeln!(html, "<div class=\"sticker\">sticker: {}</div>", escape(&sticker.name));
+        }
+        if !message.reactions.is_empty() {
+            html.push_str("<div class=\"reactions\">");
+            for reaction in &message.reactions {
+                let emoji = match &reaction.emoji {
+                    ReactionType::Custom {
+                        animated,
+                        id,
+                        name,
+                    } => emoji_image(*animated, name.as_deref().unwrap_or("deleted emoji"), &id.to_string()),
+                    ReactionType::Unicode { name } => escape(name),
+                };
+                let _ = write!(html, "<span class=\"reaction\">{emoji} {}</span>", reaction.count);
+            }
+            html.push_str("</div>\n");
+        }
+
+        html.push_str("</div>\n</div>\n");
+    }
+
+    /// The line quoting the message `message` replies to, which links to it
+    /// when it's in the transcript too
+    fn reply(&self, message: &Message) -> String {
+        let Some(replied) = &message.referenced_message else {
+            return "<div class=\"reply\">↪ <em>Original message was deleted</em></div>\n".to_owned();
+        };
+
+        let mut preview: String = replied.content.chars().take(REPLY_PREVIEW_LENGTH).collect();
+        if preview.is_empty() {
+            "Click to see attachment".clone_into(&mut preview);
+        } else if preview.len() < replied.content.len() {
+            preview.push('…');
+        }
+
+        format!(
+            "<div class=\"reply\">↪ <img src=\"{}\" alt=\"\"><span class=\"author\">{}</span><a href=\"#m{}\">{}</a></div>\n",
+            self.source(&avatar(replied)),
+            escape(&display_name(replied)),
+            replied.id,
+            escape(&preview)
+        )
+    }
+
+    fn embed(&self, embed: &Embed) -> String {
+        let mut html = format!(
+            "<div class=\"embed\" style=\"border-color: #{:06x}\">",
+            embed.color.unwrap_or(0x1e_1f22)
+        );
+
+        if let Some(thumbnail) = &embed.thumbnail {
+            let _ = write!(html, "<img class=\"embed-thumbnail\" src=\"{}\" alt=\"\">", self.source(&thumbnail.url));
+        }
+        if let Some(author) = &embed.author {
+            let _ = write!(html, "<div class=\"embed-author\">{}</div>", link(author.url.as_deref(), &escape(&author.name)));
+        }
+        if let Some(title) = &embed.title {
+            let _ = write!(html, "<div class=\"embed-title\">{}</div>", link(embed.url.as_deref(), &self.markdown(title)));
+        }
+        if let Some(description) = &embed.description {
+            let _ = write!(html, "<div class=\"embed-description\">{}</div>", self.markdown(description));
+        }
+        if !embed.fields.is_empty() {
+            html.push_str("<div class=\"fields\">");
+            for field in &embed.fields {
+                let _ = write!(
+                    html,
+                    "<div class=\"field{}\"><div class=\"field-name\">{}</div><div>{}</div></div>",
+                    if field.inline { " inline" } else { "" },
+                    self.markdown(&field.name),
+                    self.markdown(&field.value)
+                );
+            }
+            html.push_str("</div>");
+        }
+        if let Some(image) = &embed.image {
+            let _ = write!(html, "<img class=\"embed-image\" src=\"{}\" alt=\"\">", self.source(&image.url));
+        }
+        if embed.footer.is_some() || embed.timestamp.is_some() {
+            let footer = [
+                embed.footer.as_ref().map(|footer| escape(&footer.text)),
+                embed.timestamp.map(format_time),
+            ];
+            let footer = footer.into_iter().flatten().collect::<Vec<_>>();
+            let _ = write!(html, "<div class=\"embed-footer\">{}</div>", footer.join(" • "));
+        }
+
+        html.push_str("</div>\n");
+        html
+    }
+
+    /// Where an image is loaded from, inlined if it was downloaded
+    fn source(&self, url: &str) -> String {
+        self.images.get(url).map_or_else(|| escape(url), Clone::clone)
+    }
+
+    /// `text` rendered with the parts of Discord's markdown that can be
+    /// shown without a script
+    fn markdown(&self, text: &str) -> String {
+        let mut html = String::new();
+        let mut last = 0;
+        for captures in self.code_block.captures_iter(text) {
+            let block = captures.get(0).unwrap();
+            html.push_str(&self.lines(after_block(&text[last..block.start()], last)));
+            let _ = write!(html, "<pre><code>{}</code></pre>", escape(captures[1].trim_end_matches('\n')));
+            last = block.end();
+        }
+        html.push_str(&self.lines(after_block(&text[last..], last)));
+        html
+    }
+
+    /// Headers, quotes and subtexts, which only apply to whole lines
+    fn lines(&self, text: &str) -> String {
+        let mut html = String::new();
+        let mut quoting_rest = false;
+        let mut previous_inline = false;
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix(">>> ") {
+                quoting_rest = true;
+                html.push_str(&self.block("blockquote", "", rest));
+                previous_inline = false;
+                continue;
+            }
+            if quoting_rest {
+                html.push_str(&self.block("blockquote", "", line));
+                continue;
+            }
+
+            let block = [
+                ("# ", "h1", ""),
+                ("## ", "h2", ""),
+                ("### ", "h3", ""),
+                ("-# ", "div", " class=\"subtext\""),
+                ("> ", "blockquote", ""),
+            ]
+            .into_iter()
+            .find_map(|(prefix, tag, attributes)| {
+                line.strip_prefix(prefix).map(|rest| self.block(tag, attributes, rest))
+            });
+            if let Some(block) = block {
+                html.push_str(&block);
+                previous_inline = false;
+            } else {
+                if previous_inline {
+                    html.push('\n');
+                }
+                html.push_str(&self.inline(line));
+                previous_inline = true;
+            }
+        }
+        html
+    }
+
+    fn block(&self, tag: &str, attributes: &str, text: &str) -> String {
+        format!("<{tag}{attributes}>{}</{tag}>", self.inline(text))
+    }
+
+    /// `line` with its tokens rendered, then escaped and formatted around
+    /// them so that formatting can't break a link or a mention
+    fn inline(&self, line: &str) -> String {
+        let mut tokens = Vec::new();
+        let line = self.token.replace_all(line, |captures: &Captures| {
+            tokens.push(self.render_token(captures));
+            format!("\x00{}\x00", tokens.len() - 1)
+        });
+
+        let html = self.format(&escape(&line));
+        self.placeholder
+            .replace_all(&html, |captures: &Captures| {
+                captures[1]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|idx| tokens.get(idx))
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .into_owned()
+    }
+
+    fn format(&self, html: &str) -> String {
+        self.formatting
+            .iter()
+            .fold(html.to_owned(), |html, (re, replacement)| {
+                re.replace_all(&html, *replacement).into_owned()
+            })
+    }
+
+    fn render_token(&self, captures: &Captures) -> String {
+        let group = |name| captures.name(name).map(|group| group.as_str());
+        if let Some(code) = group("code") {
+            return format!("<code>{}</code>", escape(code));
+        }
+        if let (Some(text), Some(href)) = (group("text"), group("href")) {
+            return link(Some(href), &self.format(&escape(text)));
+        }
+        if let Some(url) = group("url") {
+            return link(Some(url), &escape(url));
+        }
+        if let Some(emoji) = group("emoji") {
+            return emoji_image(!captures["animated"].is_empty(), &captures["emoji_name"], emoji);
+        }
+        if let Some(user) = group("user") {
+            return mention('@', name(&self.names.users, user));
+        }
+        if let Some(role) = group("role") {
+            return mention('@', name(&self.names.roles, role));
+        }
+        if let Some(channel) = group("channel") {
+            return mention('#', name(&self.names.channels, channel));
+        }
+
+        group("time")
+            .and_then(|secs| secs.parse().ok())
+            .and_then(|secs| Timestamp::from_secs(secs).ok())
+            .map_or_else(|| escape(&captures[0]), |timestamp| format!("<code>{}</code>", format_time(timestamp)))
+    }
+}
+
+/// `text` without the line break ending the code block before it if any,
+/// the block already ends the line
+fn after_block(text: &str, start: usize) -> &str {
+    if start == 0 {
+        text
+    } else {
+        text.strip_prefix('\n').unwrap_or(text)
+    }
+}
+
+/// The name of the user, role or channel with the ID `id`, the ID itself if
+/// it's unknown
+fn name<'a>(names: &'a HashMap<u64, String>, id: &'a str) -> &'a str {
+    id.parse::<u64>()
+        .ok()
+        .and_then(|id| names.get(&id))
+        .map_or(id, String::as_str)
+}
+
+fn mention(prefix: char, name: &str) -> String {
+    format!("<span class=\"mention\">{prefix}{}</span>", escape(name))
+}
+
+fn link(url: Option<&str>, html: &str) -> String {
+    url.map_or_else(|| html.to_owned(), |url| format!("<a href=\"{}\">{html}</a>", escape(url)))
+}
+
+fn emoji_image(animated: bool, name: &str, id: &str) -> String {
+    let extension = if animated { "gif" } else { "png" };
+    let name = escape(name);
+    format!("<img class=\"emoji\" src=\"https://cdn.discordapp.com/emojis/{id}.{extension}\" alt=\":{name}:\" title=\":{name}:\">")
+}
+
+/// Like `2024-01-31 18:05 UTC`
+fn format_time(timestamp: Timestamp) -> String {
+    let iso = timestamp.iso_8601().to_string();
+    format!("{} {} UTC", &iso[..10], &iso[11..16])
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{escape, Names, Transcript};
+
+    fn transcript() -> Transcript {
+        let mut names = Names::default();
+        names.users.insert(1, "alice".to_owned());
+        names.roles.insert(2, "mods".to_owned());
+        names.channels.insert(3, "general".to_owned());
+        Transcript::new(names, HashMap::new())
+    }
+
+    #[test]
+    fn escape_html() {
+        assert_eq!(
+            escape(r#"<b onclick="x">&'</b>"#),
+            "&lt;b onclick=&quot;x&quot;&gt;&amp;&#39;&lt;/b&gt;"
+        );
+    }
+
+    #[test]
+    fn inline_escapes_and_formats() {
+        let transcript = transcript();
+
+        assert_eq!(transcript.inline("<script>"), "&lt;script&gt;");
+        assert_eq!(
+            transcript.inline("**bold** *it* __under__ ~~gone~~ ||secret||"),
+            "<strong>bold</strong> <em>it</em> <u>under</u> <s>gone</s> <span class=\"spoiler\">secret</span>"
+        );
+    }
+
+    #[test]
+    fn inline_code_isnt_formatted() {
+        assert_eq!(
+            transcript().inline("`**<b>**` **x**"),
+            "<code>**&lt;b&gt;**</code> <strong>x</strong>"
+        );
+    }
+
+    #[test]
+    fn links() {
+        let transcript = transcript();
+
+        assert_eq!(
+            transcript.inline("see https://example.com/a_b_c."),
+            "see <a href=\"https://example.com/a_b_c\">https://example.com/a_b_c</a>."
+        );
+        assert_eq!(
+            transcript.inline("[**docs**](<https://example.com/?a=1&b=2>)"),
+            "<a href=\"https://example.com/?a=1&amp;b=2\"><strong>docs</strong></a>"
+        );
+        assert_eq!(transcript.inline("[x](javascript:alert(1))"), "[x](javascript:alert(1))");
+    }
+
+    #[test]
+    fn mentions() {
+        let transcript = transcript();
+
+        assert_eq!(
+            transcript.inline("<@1> <@!1> <@&2> <#3>"),
+            "<span class=\"mention\">@alice</span> <span class=\"mention\">@alice</span> \
+             <span class=\"mention\">@mods</span> <span class=\"mention\">#general</span>"
+        );
+        assert_eq!(transcript.inline("<@4>"), "<span class=\"mention\">@4</span>");
+        assert_eq!(transcript.inline("<t:0:R>"), "<code>1970-01-01 00:00 UTC</code>");
+    }
+
+    #[test]
+    fn code_blocks() {
+        assert_eq!(
+            transcript().markdown("before\n```rust\nlet x = \"<y>\";\n**z**\n```\nafter"),
+            "before<pre><code>let x = &quot;&lt;y&gt;&quot;;\n**z**</code></pre>after"
+        );
+    }
+
+    #[test]
+    fn lines() {
+        assert_eq!(
+            transcript().lines("# title\n> quote\nplain\ntext\n-# small"),
+            "<h1>title</h1><blockquote>quote</blockquote>plain\ntext<div class=\"subtext\">small</div>"
+        );
+        assert_eq!(
+            transcript().lines(">>> all\nthe rest"),
+            "<blockquote>all</blockquote><blockquote>the rest</blockquote>"
+        );
+    }
+}